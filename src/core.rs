@@ -1,11 +1,30 @@
+pub mod ast_json;
 pub mod ast_printer;
+pub mod ast_reader;
+pub mod cli;
+pub mod cst;
+pub mod environment;
+pub mod error;
 pub mod error_reporter;
 pub mod expr;
+pub mod formatter;
 pub mod interpreter;
 pub mod lox;
+pub mod lox_class;
+pub mod lox_function;
+pub mod lox_instance;
+pub mod optimizer;
 pub mod parser;
+pub mod resolver;
 pub mod scanner;
+pub mod span;
 pub mod stmt;
 pub mod test_utils;
 pub mod token;
 pub mod token_type;
+pub mod vm;
+
+pub use error::{
+    LoxError, ParseError, ParseErrorKind, ReadError, ResolveError, ResolveErrorKind, RuntimeError,
+    RuntimeErrorKind, ScanError, ScanErrorKind, StackFrame,
+};
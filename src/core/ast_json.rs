@@ -0,0 +1,307 @@
+use serde_json::{json, Map, Value as Json};
+
+use super::{
+    expr::{Expr, Value},
+    span::Span,
+    stmt::{FunctionDecl, Stmt},
+    token::Token,
+};
+
+/// Serializes syntax trees to JSON for external tools. Every node is an
+/// object with its `kind`, its `span`, the `token` it is named after if any,
+/// and its `children` in source order.
+pub struct JsonExporter;
+
+impl Default for JsonExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JsonExporter {
+    pub fn new() -> JsonExporter {
+        JsonExporter {}
+    }
+
+    /// Exports a program as an array of statement nodes.
+    pub fn export_program(&self, statements: &[Stmt]) -> Json {
+        Json::Array(
+            statements
+                .iter()
+                .map(|statement| self.export_stmt(statement))
+                .collect(),
+        )
+    }
+
+    pub fn export_stmt(&self, statement: &Stmt) -> Json {
+        let span = statement.span();
+        match statement {
+            Stmt::Expression { expression, .. } => {
+                node("Expression", span, None, vec![self.export_expr(expression)])
+            }
+            Stmt::Print { expression, .. } => {
+                node("Print", span, None, vec![self.export_expr(expression)])
+            }
+            Stmt::Var {
+                name, initializer, ..
+            } => node(
+                "Var",
+                span,
+                Some(name),
+                initializer
+                    .iter()
+                    .map(|initializer| self.export_expr(initializer))
+                    .collect(),
+            ),
+            Stmt::Block { statements, .. } => node(
+                "Block",
+                span,
+                None,
+                statements
+                    .iter()
+                    .map(|statement| self.export_stmt(statement))
+                    .collect(),
+            ),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let mut children = vec![self.export_expr(condition), self.export_stmt(then_branch)];
+                if let Some(else_branch) = else_branch {
+                    children.push(self.export_stmt(else_branch));
+                }
+                node("If", span, None, children)
+            }
+            Stmt::While {
+                condition, body, ..
+            } => node(
+                "While",
+                span,
+                None,
+                vec![self.export_expr(condition), self.export_stmt(body)],
+            ),
+            Stmt::Function(declaration) => self.export_function(declaration),
+            Stmt::Return { keyword, value, .. } => node(
+                "Return",
+                span,
+                Some(keyword),
+                value.iter().map(|value| self.export_expr(value)).collect(),
+            ),
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => {
+                let mut children: Vec<Json> = superclass
+                    .iter()
+                    .map(|superclass| self.export_expr(superclass))
+                    .collect();
+                children.extend(methods.iter().map(|method| self.export_function(method)));
+                let mut class = node("Class", span, Some(name), children);
+                class["superclass"] = json!(superclass.is_some());
+                class
+            }
+        }
+    }
+
+    pub fn export_expr(&self, expression: &Expr) -> Json {
+        let span = expression.span();
+        match expression {
+            Expr::Literal { value, .. } => {
+                let mut literal = node("Literal", span, None, Vec::new());
+                literal["value"] = match value {
+                    Some(Value::Number(value)) => json!(value),
+                    Some(Value::String(value)) => json!(value),
+                    Some(Value::True) => json!(true),
+                    Some(Value::False) => json!(false),
+                    Some(value @ (Value::Callable(_) | Value::Class(_) | Value::Instance(_))) => {
+                        json!(value.to_string())
+                    }
+                    Some(Value::Nil) | None => Json::Null,
+                };
+                literal
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => node(
+                "Binary",
+                span,
+                Some(operator),
+                vec![self.export_expr(left), self.export_expr(right)],
+            ),
+            Expr::Grouping { expression, .. } => {
+                node("Grouping", span, None, vec![self.export_expr(expression)])
+            }
+            Expr::Unary {
+                operator, right, ..
+            } => node("Unary", span, Some(operator), vec![self.export_expr(right)]),
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => node(
+                "Ternary",
+                span,
+                None,
+                vec![
+                    self.export_expr(condition),
+                    self.export_expr(then_branch),
+                    self.export_expr(else_branch),
+                ],
+            ),
+            Expr::Variable { name, .. } => node("Variable", span, Some(name), Vec::new()),
+            Expr::Assign { name, value, .. } => {
+                node("Assign", span, Some(name), vec![self.export_expr(value)])
+            }
+            Expr::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } => node(
+                "Logical",
+                span,
+                Some(operator),
+                vec![self.export_expr(left), self.export_expr(right)],
+            ),
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+                ..
+            } => {
+                let mut children = vec![self.export_expr(callee)];
+                children.extend(arguments.iter().map(|argument| self.export_expr(argument)));
+                node("Call", span, Some(paren), children)
+            }
+            Expr::Get { object, name, .. } => {
+                node("Get", span, Some(name), vec![self.export_expr(object)])
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+                ..
+            } => node(
+                "Set",
+                span,
+                Some(name),
+                vec![self.export_expr(object), self.export_expr(value)],
+            ),
+            Expr::This { keyword, .. } => node("This", span, Some(keyword), Vec::new()),
+            Expr::Super {
+                keyword, method, ..
+            } => {
+                let mut node = node("Super", span, Some(keyword), Vec::new());
+                node["method"] = token(method);
+                node
+            }
+        }
+    }
+
+    fn export_function(&self, declaration: &FunctionDecl) -> Json {
+        let mut function = node(
+            "Function",
+            declaration.span,
+            Some(&declaration.name),
+            declaration
+                .body
+                .iter()
+                .map(|statement| self.export_stmt(statement))
+                .collect(),
+        );
+        function["params"] = declaration.params.iter().map(token).collect();
+        function
+    }
+}
+
+fn node(kind: &str, span: Span, name: Option<&Token>, children: Vec<Json>) -> Json {
+    let mut node = Map::new();
+    node.insert("kind".to_string(), json!(kind));
+    node.insert("span".to_string(), self::span(span));
+    if let Some(name) = name {
+        node.insert("token".to_string(), token(name));
+    }
+    node.insert("children".to_string(), Json::Array(children));
+    Json::Object(node)
+}
+
+fn token(token: &Token) -> Json {
+    json!({
+        "type": token.token_type.to_string(),
+        "lexeme": token.lexeme,
+        "span": span(token.span),
+    })
+}
+
+fn span(span: Span) -> Json {
+    json!({
+        "start": span.start,
+        "length": span.length,
+        "line": span.line,
+        "column": span.column,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_utils::tests::helper_create_stmts_from_string;
+
+    fn helper_export(source: &str) -> Json {
+        let statements = helper_create_stmts_from_string(source);
+        JsonExporter::new().export_program(&statements)
+    }
+
+    #[test]
+    fn test_exports_kind_token_span_and_children() {
+        let program = helper_export("print 1 + a;");
+        let print = &program[0];
+        assert_eq!(print["kind"], "Print");
+        assert_eq!(
+            print["span"],
+            json!({"start": 0, "length": 12, "line": 1, "column": 1})
+        );
+
+        let binary = &print["children"][0];
+        assert_eq!(binary["kind"], "Binary");
+        assert_eq!(binary["token"]["type"], "Plus");
+        assert_eq!(binary["token"]["span"]["column"], 9);
+        assert_eq!(binary["children"][0]["value"], 1.0);
+        assert_eq!(binary["children"][1]["token"]["lexeme"], "a");
+    }
+
+    #[test]
+    fn test_exports_declarations() {
+        let program = helper_export("class B < A { m(x) { return super.m(x); } }");
+        let class = &program[0];
+        assert_eq!(class["kind"], "Class");
+        assert_eq!(class["superclass"], true);
+        assert_eq!(class["children"][0]["kind"], "Variable");
+
+        let method = &class["children"][1];
+        assert_eq!(method["kind"], "Function");
+        assert_eq!(method["params"][0]["lexeme"], "x");
+
+        let call = &method["children"][0]["children"][0];
+        assert_eq!(call["kind"], "Call");
+        assert_eq!(call["children"][0]["kind"], "Super");
+        assert_eq!(call["children"][0]["method"]["lexeme"], "m");
+    }
+
+    #[test]
+    fn test_literals_become_json_values() {
+        let program = helper_export("print nil; print \"s\"; print false;");
+        let values: Vec<&Json> = (0..3)
+            .map(|index| &program[index]["children"][0]["value"])
+            .collect();
+        assert_eq!(values, [&Json::Null, &json!("s"), &json!(false)]);
+    }
+}
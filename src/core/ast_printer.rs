@@ -1,23 +1,23 @@
-use super::{expr::*, token::Token, token_type::TokenType};
-
-pub fn main() {
-    let test_expr = Expr::Binary {
-        left: Box::new(Expr::Unary {
-            operator: Token::new(TokenType::Minus, "-".to_string(), None, 1),
-            right: Box::new(Expr::Literal(Some(Value::to_number("123")))),
-        }),
-        operator: Token::new(TokenType::Star, "*".to_string(), None, 1),
-        right: Box::new(Expr::Grouping {
-            expression: Box::new(Expr::Literal(Some(Value::to_number("45.67")))),
-        }),
-    };
-
-    let ast_printer = AstPrinter::new();
-    println!("{}", ast_printer.print(&test_expr));
-}
+use std::{
+    cell::{Cell, RefCell},
+    fmt::Write,
+    rc::Rc,
+};
+
+use super::{
+    expr::*,
+    stmt::{FunctionDecl, Stmt, Visitor as StmtVisitor},
+    token::Token,
+};
 
 pub struct AstPrinter;
 
+impl Default for AstPrinter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AstPrinter {
     pub fn new() -> AstPrinter {
         AstPrinter {}
@@ -27,36 +27,63 @@ impl AstPrinter {
         expr.accept(self)
     }
 
-    fn parenthesize(&self, name: &String, exprs: &Vec<&Expr>) -> String {
+    /// Prints a program, one top-level statement per line.
+    pub fn print_program(&self, statements: &[Stmt]) -> String {
+        statements
+            .iter()
+            .map(|statement| statement.accept(self))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn print_function(&self, kind: &str, declaration: &FunctionDecl) -> String {
+        let params: Vec<&str> = declaration
+            .params
+            .iter()
+            .map(|param| param.lexeme.as_str())
+            .collect();
+        let mut result = format!("({kind} {} ({})", declaration.name.lexeme, params.join(" "));
+        for statement in &declaration.body {
+            result.push(' ');
+            result.push_str(&statement.accept(self));
+        }
+        result.push(')');
+        result
+    }
+
+    fn parenthesize(&self, name: &str, exprs: &[&Expr]) -> String {
         let mut result = String::new();
-        result.push_str("(");
-        result.push_str(&name);
+        result.push('(');
+        result.push_str(name);
         for expr in exprs {
-            result.push_str(" ");
+            result.push(' ');
             result.push_str(&expr.accept(self));
         }
-        result.push_str(")");
+        result.push(')');
         result
     }
 }
 
 impl Visitor<String> for AstPrinter {
     fn visit_binary(&self, left: &Expr, operator: &Token, right: &Expr) -> String {
-        self.parenthesize(&operator.lexeme, &vec![left, right])
+        self.parenthesize(&operator.lexeme, &[left, right])
     }
 
     fn visit_grouping(&self, expr: &Expr) -> String {
-        self.parenthesize(&"group".to_string(), &vec![expr])
+        self.parenthesize("group", &[expr])
     }
 
     fn visit_literal(&self, value: &Option<Value>) -> String {
         if let Some(value) = value {
             match value {
                 Value::Number(value) => value.to_string(),
-                Value::String(value) => value.to_string(),
+                Value::String(value) => format!("\"{value}\""),
                 Value::True => "true".to_string(),
                 Value::False => "false".to_string(),
                 Value::Nil => "nil".to_string(),
+                Value::Callable(callable) => callable.to_string(),
+                Value::Class(class) => class.to_string(),
+                Value::Instance(instance) => instance.borrow().to_string(),
             }
         } else {
             "nil".to_string()
@@ -64,7 +91,7 @@ impl Visitor<String> for AstPrinter {
     }
 
     fn visit_unary(&self, operator: &Token, right: &Expr) -> String {
-        self.parenthesize(&operator.lexeme, &vec![right])
+        self.parenthesize(&operator.lexeme, &[right])
     }
 
     fn visit_ternary(&self, condition: &Expr, then_branch: &Expr, else_branch: &Expr) -> String {
@@ -75,7 +102,420 @@ impl Visitor<String> for AstPrinter {
         result.push_str(&then_branch.accept(self));
         result.push_str(" else ");
         result.push_str(&else_branch.accept(self));
-        result.push_str(")");
+        result.push(')');
+        result
+    }
+
+    fn visit_variable(&self, token: &Token, _depth: &Depth) -> String {
+        token.lexeme.clone()
+    }
+
+    fn visit_assign(&self, name: &Token, value: &Expr, _depth: &Depth) -> String {
+        self.parenthesize(&format!("= {}", name.lexeme), &[value])
+    }
+
+    fn visit_logical(&self, left: &Expr, operator: &Token, right: &Expr) -> String {
+        self.parenthesize(&operator.lexeme, &[left, right])
+    }
+
+    fn visit_call(&self, callee: &Expr, _paren: &Token, arguments: &[Expr]) -> String {
+        let mut exprs = vec![callee];
+        exprs.extend(arguments);
+        self.parenthesize("call", &exprs)
+    }
+
+    fn visit_get(&self, object: &Expr, name: &Token) -> String {
+        self.parenthesize(&format!(". {}", name.lexeme), &[object])
+    }
+
+    fn visit_set(&self, object: &Expr, name: &Token, value: &Expr) -> String {
+        self.parenthesize(&format!("= {}", name.lexeme), &[object, value])
+    }
+
+    fn visit_this(&self, _keyword: &Token, _depth: &Depth) -> String {
+        "this".to_string()
+    }
+
+    fn visit_super(&self, _keyword: &Token, method: &Token, _depth: &Depth) -> String {
+        format!("(super {})", method.lexeme)
+    }
+}
+
+impl StmtVisitor<String> for AstPrinter {
+    fn visit_expression(&self, expr: &Expr) -> String {
+        self.parenthesize(";", &[expr])
+    }
+
+    fn visit_print(&self, expr: &Expr) -> String {
+        self.parenthesize("print", &[expr])
+    }
+
+    fn visit_var(&self, name: &Token, initializer: &Option<Expr>) -> String {
+        match initializer {
+            Some(initializer) => self.parenthesize(&format!("var {}", name.lexeme), &[initializer]),
+            None => format!("(var {})", name.lexeme),
+        }
+    }
+
+    fn visit_block(&self, statements: &[Stmt]) -> String {
+        let mut result = "(block".to_string();
+        for statement in statements {
+            result.push(' ');
+            result.push_str(&statement.accept(self));
+        }
+        result.push(')');
         result
     }
+
+    fn visit_if(
+        &self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: &Option<Box<Stmt>>,
+    ) -> String {
+        let mut result = format!(
+            "(if {} {}",
+            condition.accept(self),
+            then_branch.accept(self)
+        );
+        if let Some(else_branch) = else_branch {
+            result.push(' ');
+            result.push_str(&else_branch.accept(self));
+        }
+        result.push(')');
+        result
+    }
+
+    fn visit_while(&self, condition: &Expr, body: &Stmt) -> String {
+        format!("(while {} {})", condition.accept(self), body.accept(self))
+    }
+
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> String {
+        self.print_function("fun", declaration)
+    }
+
+    fn visit_return(&self, _keyword: &Token, value: &Option<Expr>) -> String {
+        match value {
+            Some(value) => self.parenthesize("return", &[value]),
+            None => "(return)".to_string(),
+        }
+    }
+
+    fn visit_class(
+        &self,
+        name: &Token,
+        superclass: &Option<Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) -> String {
+        let mut result = format!("(class {}", name.lexeme);
+        if let Some(superclass) = superclass {
+            result.push_str(" < ");
+            result.push_str(&superclass.accept(self));
+        }
+        for method in methods {
+            result.push(' ');
+            result.push_str(&self.print_function("method", method));
+        }
+        result.push(')');
+        result
+    }
+}
+
+/// Renders a program as a Graphviz `digraph`, with one node per statement
+/// and expression labelled by its operator, name or literal value.
+pub struct DotPrinter {
+    output: RefCell<String>,
+    next_id: Cell<usize>,
+}
+
+impl Default for DotPrinter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DotPrinter {
+    pub fn new() -> DotPrinter {
+        DotPrinter {
+            output: RefCell::new(String::new()),
+            next_id: Cell::new(0),
+        }
+    }
+
+    pub fn print_program(&self, statements: &[Stmt]) -> String {
+        self.output
+            .replace("digraph ast {\n  node [shape=box];\n".to_string());
+        self.next_id.set(0);
+        let program = self.node("program");
+        for statement in statements {
+            let child = statement.accept(self);
+            self.edge(program, child, None);
+        }
+        let mut output = self.output.take();
+        output.push('}');
+        output
+    }
+
+    /// Adds a node and returns its id.
+    fn node(&self, label: &str) -> usize {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        let _ = writeln!(
+            self.output.borrow_mut(),
+            "  n{id} [label=\"{}\"];",
+            escape(label)
+        );
+        id
+    }
+
+    fn edge(&self, from: usize, to: usize, label: Option<&str>) {
+        let mut output = self.output.borrow_mut();
+        let _ = match label {
+            Some(label) => writeln!(output, "  n{from} -> n{to} [label=\"{label}\"];"),
+            None => writeln!(output, "  n{from} -> n{to};"),
+        };
+    }
+
+    /// Adds a node with an edge to each of `exprs`.
+    fn parent(&self, label: &str, exprs: &[&Expr]) -> usize {
+        let id = self.node(label);
+        for expr in exprs {
+            let child = expr.accept(self);
+            self.edge(id, child, None);
+        }
+        id
+    }
+
+    fn function(&self, kind: &str, declaration: &FunctionDecl) -> usize {
+        let params: Vec<&str> = declaration
+            .params
+            .iter()
+            .map(|param| param.lexeme.as_str())
+            .collect();
+        let id = self.node(&format!(
+            "{kind} {}({})",
+            declaration.name.lexeme,
+            params.join(", ")
+        ));
+        for statement in &declaration.body {
+            let child = statement.accept(self);
+            self.edge(id, child, None);
+        }
+        id
+    }
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Visitor<usize> for DotPrinter {
+    fn visit_binary(&self, left: &Expr, operator: &Token, right: &Expr) -> usize {
+        self.parent(&operator.lexeme, &[left, right])
+    }
+
+    fn visit_grouping(&self, expr: &Expr) -> usize {
+        self.parent("group", &[expr])
+    }
+
+    fn visit_literal(&self, value: &Option<Value>) -> usize {
+        self.node(&AstPrinter::new().visit_literal(value))
+    }
+
+    fn visit_unary(&self, operator: &Token, right: &Expr) -> usize {
+        self.parent(&operator.lexeme, &[right])
+    }
+
+    fn visit_ternary(&self, condition: &Expr, then_branch: &Expr, else_branch: &Expr) -> usize {
+        let id = self.node("?:");
+        for (label, branch) in [
+            ("if", condition),
+            ("then", then_branch),
+            ("else", else_branch),
+        ] {
+            let child = branch.accept(self);
+            self.edge(id, child, Some(label));
+        }
+        id
+    }
+
+    fn visit_variable(&self, token: &Token, _depth: &Depth) -> usize {
+        self.node(&token.lexeme)
+    }
+
+    fn visit_assign(&self, name: &Token, value: &Expr, _depth: &Depth) -> usize {
+        self.parent(&format!("= {}", name.lexeme), &[value])
+    }
+
+    fn visit_logical(&self, left: &Expr, operator: &Token, right: &Expr) -> usize {
+        self.parent(&operator.lexeme, &[left, right])
+    }
+
+    fn visit_call(&self, callee: &Expr, _paren: &Token, arguments: &[Expr]) -> usize {
+        let mut exprs = vec![callee];
+        exprs.extend(arguments);
+        self.parent("call", &exprs)
+    }
+
+    fn visit_get(&self, object: &Expr, name: &Token) -> usize {
+        self.parent(&format!(".{}", name.lexeme), &[object])
+    }
+
+    fn visit_set(&self, object: &Expr, name: &Token, value: &Expr) -> usize {
+        self.parent(&format!(".{} =", name.lexeme), &[object, value])
+    }
+
+    fn visit_this(&self, _keyword: &Token, _depth: &Depth) -> usize {
+        self.node("this")
+    }
+
+    fn visit_super(&self, _keyword: &Token, method: &Token, _depth: &Depth) -> usize {
+        self.node(&format!("super.{}", method.lexeme))
+    }
+}
+
+impl StmtVisitor<usize> for DotPrinter {
+    fn visit_expression(&self, expr: &Expr) -> usize {
+        self.parent(";", &[expr])
+    }
+
+    fn visit_print(&self, expr: &Expr) -> usize {
+        self.parent("print", &[expr])
+    }
+
+    fn visit_var(&self, name: &Token, initializer: &Option<Expr>) -> usize {
+        let initializer: Vec<&Expr> = initializer.iter().collect();
+        self.parent(&format!("var {}", name.lexeme), &initializer)
+    }
+
+    fn visit_block(&self, statements: &[Stmt]) -> usize {
+        let id = self.node("block");
+        for statement in statements {
+            let child = statement.accept(self);
+            self.edge(id, child, None);
+        }
+        id
+    }
+
+    fn visit_if(
+        &self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: &Option<Box<Stmt>>,
+    ) -> usize {
+        let id = self.node("if");
+        let child = condition.accept(self);
+        self.edge(id, child, Some("if"));
+        let child = then_branch.accept(self);
+        self.edge(id, child, Some("then"));
+        if let Some(else_branch) = else_branch {
+            let child = else_branch.accept(self);
+            self.edge(id, child, Some("else"));
+        }
+        id
+    }
+
+    fn visit_while(&self, condition: &Expr, body: &Stmt) -> usize {
+        let id = self.node("while");
+        let child = condition.accept(self);
+        self.edge(id, child, Some("if"));
+        let child = body.accept(self);
+        self.edge(id, child, Some("do"));
+        id
+    }
+
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> usize {
+        self.function("fun", declaration)
+    }
+
+    fn visit_return(&self, _keyword: &Token, value: &Option<Expr>) -> usize {
+        let value: Vec<&Expr> = value.iter().collect();
+        self.parent("return", &value)
+    }
+
+    fn visit_class(
+        &self,
+        name: &Token,
+        superclass: &Option<Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) -> usize {
+        let id = self.node(&format!("class {}", name.lexeme));
+        if let Some(superclass) = superclass {
+            let child = superclass.accept(self);
+            self.edge(id, child, Some("<"));
+        }
+        for method in methods {
+            let child = self.function("method", method);
+            self.edge(id, child, None);
+        }
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_utils::tests::helper_create_stmts_from_string;
+
+    #[test]
+    fn test_prints_statements() {
+        let statements = helper_create_stmts_from_string(
+            "var a = 1;
+             if (a) print a; else { a = 2; }
+             fun f(x, y) { return x + y; }
+             class B < A { m() { return; } }",
+        );
+        assert_eq!(
+            AstPrinter::new().print_program(&statements),
+            "(var a 1)\n\
+             (if a (print a) (block (; (= a 2))))\n\
+             (fun f (x y) (return (+ x y)))\n\
+             (class B < A (method m () (return)))"
+        );
+    }
+
+    #[test]
+    fn test_dot_shows_ternary_and_comma_precedence() {
+        let statements = helper_create_stmts_from_string("print a, b ? \"x\" : c;");
+        assert_eq!(
+            DotPrinter::new().print_program(&statements),
+            "digraph ast {\n  \
+             node [shape=box];\n  \
+             n0 [label=\"program\"];\n  \
+             n1 [label=\"print\"];\n  \
+             n2 [label=\",\"];\n  \
+             n3 [label=\"a\"];\n  \
+             n2 -> n3;\n  \
+             n4 [label=\"?:\"];\n  \
+             n5 [label=\"b\"];\n  \
+             n4 -> n5 [label=\"if\"];\n  \
+             n6 [label=\"\\\"x\\\"\"];\n  \
+             n4 -> n6 [label=\"then\"];\n  \
+             n7 [label=\"c\"];\n  \
+             n4 -> n7 [label=\"else\"];\n  \
+             n2 -> n4;\n  \
+             n1 -> n2;\n  \
+             n0 -> n1;\n\
+             }"
+        );
+    }
+
+    #[test]
+    fn test_dot_labels_statements() {
+        let statements = helper_create_stmts_from_string(
+            "class B < A { m(x, y) { return; } } while (true) { var z; }",
+        );
+        let dot = DotPrinter::new().print_program(&statements);
+        for label in [
+            "class B",
+            "method m(x, y)",
+            "return",
+            "while",
+            "block",
+            "var z",
+        ] {
+            assert!(dot.contains(&format!("[label=\"{label}\"]")), "{label}");
+        }
+        assert!(dot.contains("n1 -> n2 [label=\"<\"];"));
+    }
 }
@@ -0,0 +1,458 @@
+use super::{
+    error::ReadError,
+    expr::{Depth, Expr, Value},
+    span::Span,
+    token::Token,
+    token_type::TokenType,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+enum ItemKind {
+    Open,
+    Close,
+    Atom(String),
+    String(String),
+}
+
+#[derive(Debug, Clone)]
+struct Item {
+    kind: ItemKind,
+    span: Span,
+}
+
+/// Reads the S-expressions written by `AstPrinter::print` back into an
+/// `Expr`. The tokens in the tree get spans pointing into the S-expression
+/// text, since the Lox source it came from is gone.
+pub struct AstReader {
+    source: String,
+    items: Vec<Item>,
+    current: usize,
+}
+
+impl AstReader {
+    pub fn new(source: &str) -> AstReader {
+        AstReader {
+            source: source.to_string(),
+            items: Vec::new(),
+            current: 0,
+        }
+    }
+
+    /// Reads the single expression that makes up the whole input.
+    pub fn read_expr(&mut self) -> Result<Expr, ReadError> {
+        self.items = self.scan()?;
+        self.current = 0;
+
+        let expr = self.expr()?;
+        if let Some(item) = self.items.get(self.current) {
+            return Err(ReadError::new(item.span, "Expect end of input."));
+        }
+        Ok(expr)
+    }
+
+    fn scan(&self) -> Result<Vec<Item>, ReadError> {
+        let mut items = Vec::new();
+        let mut chars = self.source.char_indices().peekable();
+        let (mut line, mut column) = (1, 1);
+
+        while let Some((start, c)) = chars.next() {
+            let (start_line, start_column) = (line, column);
+            let mut length = c.len_utf8();
+            advance(c, &mut line, &mut column);
+
+            let kind = match c {
+                '(' => ItemKind::Open,
+                ')' => ItemKind::Close,
+                c if c.is_whitespace() => continue,
+                '"' => {
+                    let mut value = String::new();
+                    loop {
+                        let Some((_, c)) = chars.next() else {
+                            let span = Span::new(start, length, start_line, start_column);
+                            return Err(ReadError::new(span, "Unterminated string."));
+                        };
+                        length += c.len_utf8();
+                        advance(c, &mut line, &mut column);
+                        if c == '"' {
+                            break;
+                        }
+                        value.push(c);
+                    }
+                    ItemKind::String(value)
+                }
+                c => {
+                    let mut atom = c.to_string();
+                    while let Some(&(_, c)) = chars.peek() {
+                        if c.is_whitespace() || matches!(c, '(' | ')' | '"') {
+                            break;
+                        }
+                        atom.push(c);
+                        length += c.len_utf8();
+                        advance(c, &mut line, &mut column);
+                        chars.next();
+                    }
+                    ItemKind::Atom(atom)
+                }
+            };
+            items.push(Item {
+                kind,
+                span: Span::new(start, length, start_line, start_column),
+            });
+        }
+        Ok(items)
+    }
+
+    fn expr(&mut self) -> Result<Expr, ReadError> {
+        let item = self.advance()?;
+        match item.kind {
+            ItemKind::Open => self.list(item.span),
+            ItemKind::Close => Err(ReadError::new(item.span, "Unexpected ')'.")),
+            ItemKind::String(value) => Ok(Expr::Literal {
+                value: Some(Value::String(value)),
+                span: item.span,
+            }),
+            ItemKind::Atom(atom) => atom_expr(&atom, item.span),
+        }
+    }
+
+    /// Reads the rest of a list whose `(` has been consumed.
+    fn list(&mut self, open: Span) -> Result<Expr, ReadError> {
+        let head = self.advance()?;
+        let ItemKind::Atom(operator) = &head.kind else {
+            return Err(ReadError::new(head.span, "Expect an operator."));
+        };
+
+        let expr = match operator.as_str() {
+            "group" => {
+                let expression = Box::new(self.expr()?);
+                let span = self.close(open)?;
+                Expr::Grouping { expression, span }
+            }
+            "if" => {
+                let condition = Box::new(self.expr()?);
+                self.keyword("then")?;
+                let then_branch = Box::new(self.expr()?);
+                self.keyword("else")?;
+                let else_branch = Box::new(self.expr()?);
+                let span = self.close(open)?;
+                Expr::Ternary {
+                    condition,
+                    then_branch,
+                    else_branch,
+                    span,
+                }
+            }
+            "=" => {
+                let name = self.name()?;
+                let first = Box::new(self.expr()?);
+                if self.check_close() {
+                    let span = self.close(open)?;
+                    Expr::Assign {
+                        name,
+                        value: first,
+                        depth: Depth::default(),
+                        span,
+                    }
+                } else {
+                    let value = Box::new(self.expr()?);
+                    let span = self.close(open)?;
+                    Expr::Set {
+                        object: first,
+                        name,
+                        value,
+                        span,
+                    }
+                }
+            }
+            "." => {
+                let name = self.name()?;
+                let object = Box::new(self.expr()?);
+                let span = self.close(open)?;
+                Expr::Get { object, name, span }
+            }
+            "call" => {
+                let callee = Box::new(self.expr()?);
+                let mut arguments = Vec::new();
+                while !self.check_close() {
+                    arguments.push(self.expr()?);
+                }
+                let close = self.close_paren()?;
+                let paren = Token::new(TokenType::RightParen, ")".to_string(), None, close);
+                Expr::Call {
+                    callee,
+                    paren,
+                    arguments,
+                    span: open.to(close),
+                }
+            }
+            "super" => {
+                let keyword = Token::new(TokenType::Super, "super".to_string(), None, head.span);
+                let method = self.name()?;
+                let span = self.close(open)?;
+                Expr::Super {
+                    keyword,
+                    method,
+                    depth: Depth::default(),
+                    span,
+                }
+            }
+            _ => {
+                let Some(token_type) = operator_type(operator) else {
+                    return Err(ReadError::new(head.span, "Unknown operator."));
+                };
+                let operator = Token::new(token_type, operator.clone(), None, head.span);
+                let mut operands = Vec::new();
+                while !self.check_close() {
+                    operands.push(self.expr()?);
+                }
+                let span = self.close(open)?;
+                operation(operator, operands, span)?
+            }
+        };
+        Ok(expr)
+    }
+
+    fn advance(&mut self) -> Result<Item, ReadError> {
+        match self.items.get(self.current) {
+            Some(item) => {
+                self.current += 1;
+                Ok(item.clone())
+            }
+            None => Err(ReadError::new(self.end_span(), "Unexpected end of input.")),
+        }
+    }
+
+    fn check_close(&self) -> bool {
+        matches!(
+            self.items.get(self.current),
+            Some(Item {
+                kind: ItemKind::Close,
+                ..
+            })
+        )
+    }
+
+    /// Consumes the `)` closing the list opened at `open` and returns the
+    /// span of the whole list.
+    fn close(&mut self, open: Span) -> Result<Span, ReadError> {
+        Ok(open.to(self.close_paren()?))
+    }
+
+    fn close_paren(&mut self) -> Result<Span, ReadError> {
+        let item = self.advance()?;
+        match item.kind {
+            ItemKind::Close => Ok(item.span),
+            _ => Err(ReadError::new(item.span, "Expect ')'.")),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), ReadError> {
+        let item = self.advance()?;
+        match &item.kind {
+            ItemKind::Atom(atom) if atom == keyword => Ok(()),
+            _ => Err(ReadError::new(item.span, &format!("Expect '{keyword}'."))),
+        }
+    }
+
+    fn name(&mut self) -> Result<Token, ReadError> {
+        let item = self.advance()?;
+        match &item.kind {
+            ItemKind::Atom(atom) if is_identifier(atom) => Ok(Token::new(
+                TokenType::Identifier,
+                atom.clone(),
+                None,
+                item.span,
+            )),
+            _ => Err(ReadError::new(item.span, "Expect a name.")),
+        }
+    }
+
+    fn end_span(&self) -> Span {
+        let (mut line, mut column) = (1, 1);
+        for c in self.source.chars() {
+            advance(c, &mut line, &mut column);
+        }
+        Span::new(self.source.len(), 0, line, column)
+    }
+}
+
+fn advance(c: char, line: &mut u32, column: &mut u32) {
+    if c == '\n' {
+        *line += 1;
+        *column = 1;
+    } else {
+        *column += 1;
+    }
+}
+
+fn atom_expr(atom: &str, span: Span) -> Result<Expr, ReadError> {
+    let literal = |value| {
+        Ok(Expr::Literal {
+            value: Some(value),
+            span,
+        })
+    };
+    match atom {
+        "nil" => literal(Value::Nil),
+        "true" => literal(Value::True),
+        "false" => literal(Value::False),
+        "this" => Ok(Expr::This {
+            keyword: Token::new(TokenType::This, atom.to_string(), None, span),
+            depth: Depth::default(),
+        }),
+        _ if is_identifier(atom) => Ok(Expr::Variable {
+            name: Token::new(TokenType::Identifier, atom.to_string(), None, span),
+            depth: Depth::default(),
+        }),
+        _ => match atom.parse::<f64>() {
+            Ok(value) if atom.starts_with(|c: char| c.is_ascii_digit() || c == '-') => {
+                literal(Value::Number(value))
+            }
+            _ => Err(ReadError::new(span, "Expect an expression.")),
+        },
+    }
+}
+
+fn is_identifier(atom: &str) -> bool {
+    let mut chars = atom.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn operator_type(operator: &str) -> Option<TokenType> {
+    let token_type = match operator {
+        "+" => TokenType::Plus,
+        "-" => TokenType::Minus,
+        "*" => TokenType::Star,
+        "/" => TokenType::Slash,
+        "!" => TokenType::Bang,
+        "!=" => TokenType::BangEqual,
+        "==" => TokenType::EqualEqual,
+        ">" => TokenType::Greater,
+        ">=" => TokenType::GreaterEqual,
+        "<" => TokenType::Less,
+        "<=" => TokenType::LessEqual,
+        "," => TokenType::Comma,
+        "and" => TokenType::And,
+        "or" => TokenType::Or,
+        _ => return None,
+    };
+    Some(token_type)
+}
+
+/// Builds a unary, binary or logical expression from an operator and its
+/// operands.
+fn operation(operator: Token, mut operands: Vec<Expr>, span: Span) -> Result<Expr, ReadError> {
+    let unary = matches!(operator.token_type, TokenType::Minus | TokenType::Bang);
+    match operands.len() {
+        1 if unary => Ok(Expr::Unary {
+            operator,
+            right: Box::new(operands.remove(0)),
+            span,
+        }),
+        2 if operator.token_type != TokenType::Bang => {
+            let right = Box::new(operands.remove(1));
+            let left = Box::new(operands.remove(0));
+            Ok(match operator.token_type {
+                TokenType::And | TokenType::Or => Expr::Logical {
+                    left,
+                    operator,
+                    right,
+                    span,
+                },
+                _ => Expr::Binary {
+                    left,
+                    operator,
+                    right,
+                    span,
+                },
+            })
+        }
+        _ => Err(ReadError::new(
+            operator.span,
+            &format!(
+                "Wrong number of operands for '{}': {}.",
+                operator.lexeme,
+                operands.len()
+            ),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{ast_printer::AstPrinter, test_utils::tests::helper_create_expr_from_string};
+
+    fn helper_read(source: &str) -> Result<Expr, ReadError> {
+        AstReader::new(source).read_expr()
+    }
+
+    #[test]
+    fn test_round_trips_printed_expressions() {
+        let sources = [
+            "1 + 2 * -3;",
+            "!(a == \"two words\") != false;",
+            "a = b ? c : nil, d;",
+            "x and y or this;",
+            "f(1, g())(h).field;",
+            "object.field = other.field = 2.5;",
+            "super.method(1);",
+        ];
+        let printer = AstPrinter::new();
+        for source in sources {
+            let printed = printer.print(&helper_create_expr_from_string(source));
+            let read = helper_read(&printed).unwrap();
+            assert_eq!(printer.print(&read), printed, "for {source}");
+        }
+    }
+
+    #[test]
+    fn test_reads_node_kinds_and_spans() {
+        match helper_read("(- (group 1))").unwrap() {
+            Expr::Unary {
+                operator,
+                right,
+                span,
+            } => {
+                assert_eq!(operator.token_type, TokenType::Minus);
+                assert_eq!((operator.span.column, span.length), (2, 13));
+                assert!(matches!(*right, Expr::Grouping { .. }));
+            }
+            expr => panic!("Expected unary, got {expr:?}"),
+        }
+        match helper_read("(= x (. y o) 1)").unwrap() {
+            Expr::Set { name, object, .. } => {
+                assert_eq!(name.lexeme, "x");
+                assert!(matches!(*object, Expr::Get { .. }));
+            }
+            expr => panic!("Expected set, got {expr:?}"),
+        }
+    }
+
+    #[test]
+    fn test_reports_malformed_input() {
+        let error = helper_read("(+ 1").unwrap_err();
+        assert_eq!(error.message, "Unexpected end of input.");
+        let error = helper_read("(+ 1 2 3)").unwrap_err();
+        assert_eq!(error.span.column, 2);
+        assert_eq!(
+            helper_read("1 2").unwrap_err().message,
+            "Expect end of input."
+        );
+        assert_eq!(
+            helper_read("(% 1 2)").unwrap_err().message,
+            "Unknown operator."
+        );
+        assert_eq!(
+            helper_read("\"open").unwrap_err().message,
+            "Unterminated string."
+        );
+        assert_eq!(
+            helper_read("(if a b c)").unwrap_err().message,
+            "Expect 'then'."
+        );
+    }
+}
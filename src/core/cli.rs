@@ -0,0 +1,339 @@
+use std::{ffi::OsString, io::Read};
+
+use clap::{error::ErrorKind, Parser, Subcommand, ValueEnum};
+
+use super::{
+    error::{LoxError, EXIT_CANT_CREATE, EXIT_NO_INPUT, EXIT_UNFORMATTED, EXIT_USAGE},
+    formatter::FormatOptions,
+    interpreter::STACK_SIZE,
+    lox::{Backend, Lox},
+};
+
+/// A tree-walking interpreter for the Lox language.
+#[derive(Parser)]
+#[command(name = "rlox", version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    /// Run CODE instead of a script.
+    #[arg(
+        short = 'e',
+        long = "eval",
+        value_name = "CODE",
+        conflicts_with = "script"
+    )]
+    eval: Option<String>,
+
+    /// Script to run, or `-` to read it from stdin. Starts a REPL when
+    /// neither a script nor a command is given.
+    script: Option<String>,
+
+    /// Run programs on the bytecode VM instead of the tree-walking
+    /// interpreter.
+    #[arg(long, global = true)]
+    vm: bool,
+
+    /// Run programs without constant folding them first.
+    #[arg(long = "no-opt", global = true)]
+    no_opt: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a script.
+    Run {
+        /// Script to run, or `-` to read it from stdin.
+        file: String,
+    },
+    /// Start an interactive session.
+    Repl,
+    /// Print the tokens scanned from a script.
+    Tokens {
+        /// Script to scan, or `-` to read it from stdin.
+        file: String,
+    },
+    /// Print the syntax tree parsed from a script.
+    Ast {
+        /// How to print the tree.
+        #[arg(long, value_enum, default_value_t = AstFormat::Sexpr)]
+        format: AstFormat,
+        /// Script to parse, or `-` to read it from stdin.
+        file: String,
+    },
+    /// Report compile errors in a script without running it.
+    Check {
+        /// Script to check, or `-` to read it from stdin.
+        file: String,
+    },
+    /// Format a script in place, or print it formatted when read from
+    /// stdin.
+    Fmt {
+        /// Only report whether the script is formatted, exiting with 1 if
+        /// it is not.
+        #[arg(long)]
+        check: bool,
+        /// Spaces per level of indentation.
+        #[arg(long, value_name = "N", default_value_t = FormatOptions::default().indent_width)]
+        indent_width: usize,
+        /// Line length to wrap long expressions at.
+        #[arg(long, value_name = "N", default_value_t = FormatOptions::default().max_width)]
+        max_width: usize,
+        /// Script to format, or `-` to read it from stdin.
+        file: String,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum AstFormat {
+    /// Lisp-like S-expressions, one statement per line.
+    Sexpr,
+    /// JSON nodes with their kind, token, span and children.
+    Json,
+    /// A Graphviz digraph with one node per statement and expression.
+    Dot,
+}
+
+impl Cli {
+    /// Whether the command runs Lox code on the tree-walking interpreter,
+    /// whose native stack grows with every Lox call.
+    fn runs_tree_walker(&self) -> bool {
+        !self.vm
+            && matches!(
+                self.command,
+                None | Some(Command::Run { .. } | Command::Repl)
+            )
+    }
+}
+
+/// Runs the `rlox` command line and returns the process exit status.
+pub fn main() -> i32 {
+    run(std::env::args_os())
+}
+
+pub fn run<I, T>(args: I) -> i32
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let cli = match Cli::try_parse_from(args) {
+        Ok(cli) => cli,
+        Err(error) => {
+            let _ = error.print();
+            return match error.kind() {
+                ErrorKind::DisplayHelp | ErrorKind::DisplayVersion => 0,
+                _ => EXIT_USAGE,
+            };
+        }
+    };
+    if !cli.runs_tree_walker() {
+        return execute(cli);
+    }
+
+    let thread = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| execute(cli))
+        .expect("failed to spawn the interpreter thread");
+    thread
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+fn execute(cli: Cli) -> i32 {
+    let mut lox = Lox::new();
+    if cli.vm {
+        lox.set_backend(Backend::Vm);
+    }
+    lox.set_optimize(!cli.no_opt);
+    if let Some(code) = cli.eval {
+        lox.set_source_name("<eval>");
+        return lox.run_script(code);
+    }
+
+    let command = match (cli.command, cli.script) {
+        (Some(command), _) => command,
+        (None, Some(file)) => Command::Run { file },
+        (None, None) => Command::Repl,
+    };
+
+    match command {
+        Command::Repl => {
+            lox.run_prompt();
+            0
+        }
+        Command::Run { file } => {
+            with_source(&mut lox, &file, |lox, source| Ok(lox.run_script(source)))
+        }
+        Command::Tokens { file } => with_source(&mut lox, &file, |lox, source| {
+            print!("{}", lox.tokens(source)?);
+            Ok(0)
+        }),
+        Command::Ast { format, file } => with_source(&mut lox, &file, |lox, source| {
+            let ast = match format {
+                AstFormat::Sexpr => lox.ast(source)?,
+                AstFormat::Json => lox.ast_json(source)?,
+                AstFormat::Dot => lox.ast_dot(source)?,
+            };
+            println!("{ast}");
+            Ok(0)
+        }),
+        Command::Check { file } => {
+            with_source(&mut lox, &file, |lox, source| lox.check(source).map(|_| 0))
+        }
+        Command::Fmt {
+            check,
+            indent_width,
+            max_width,
+            file,
+        } => with_source(&mut lox, &file, |lox, source| {
+            let options = FormatOptions {
+                indent_width,
+                max_width,
+            };
+            let formatted = lox.format(source.clone(), options)?;
+            if check {
+                if formatted == source {
+                    return Ok(0);
+                }
+                eprintln!("{file} is not formatted");
+                return Ok(EXIT_UNFORMATTED);
+            }
+            if file == "-" {
+                print!("{formatted}");
+            } else if formatted != source {
+                if let Err(error) = std::fs::write(&file, formatted) {
+                    eprintln!("Could not write '{file}': {error}");
+                    return Ok(EXIT_CANT_CREATE);
+                }
+            }
+            Ok(0)
+        }),
+    }
+}
+
+/// Reads `file` (or stdin for `-`) and hands it to `action`, turning
+/// failures into exit statuses.
+fn with_source(
+    lox: &mut Lox,
+    file: &str,
+    action: impl FnOnce(&mut Lox, String) -> Result<i32, LoxError>,
+) -> i32 {
+    let (name, source) = if file == "-" {
+        let mut source = String::new();
+        if let Err(error) = std::io::stdin().read_to_string(&mut source) {
+            eprintln!("Could not read stdin: {error}");
+            return EXIT_NO_INPUT;
+        }
+        ("<stdin>", source)
+    } else {
+        match std::fs::read_to_string(file) {
+            Ok(source) => (file, source),
+            Err(error) => {
+                eprintln!("Could not read '{file}': {error}");
+                return EXIT_NO_INPUT;
+            }
+        }
+    };
+
+    lox.set_source_name(name);
+    action(lox, source).unwrap_or_else(|error| error.exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `source` to a temp file unique to this test run; `name` should
+    /// include the calling test's name.
+    fn helper_write_script(name: &str, source: &str) -> String {
+        let path = std::env::temp_dir().join(format!("rlox_cli_{name}_{}.lox", std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        path.display().to_string()
+    }
+
+    #[test]
+    fn test_only_tree_walker_runs_get_a_large_stack() {
+        let runs_tree_walker =
+            |args: &[&str]| Cli::try_parse_from(args).unwrap().runs_tree_walker();
+        assert!(runs_tree_walker(&["rlox", "script.lox"]));
+        assert!(runs_tree_walker(&["rlox", "-e", "1;"]));
+        assert!(runs_tree_walker(&["rlox", "repl"]));
+        assert!(!runs_tree_walker(&["rlox", "run", "--vm", "script.lox"]));
+        assert!(!runs_tree_walker(&["rlox", "tokens", "script.lox"]));
+        assert!(!runs_tree_walker(&["rlox", "fmt", "script.lox"]));
+    }
+
+    #[test]
+    fn test_usage_errors_exit_with_64() {
+        assert_eq!(run(["rlox", "--bogus"]), EXIT_USAGE);
+        assert_eq!(run(["rlox", "run"]), EXIT_USAGE);
+        assert_eq!(run(["rlox", "-e", "1;", "script.lox"]), EXIT_USAGE);
+    }
+
+    #[test]
+    fn test_help_exits_successfully() {
+        assert_eq!(run(["rlox", "--help"]), 0);
+        assert_eq!(run(["rlox", "check", "--help"]), 0);
+    }
+
+    #[test]
+    fn test_eval_runs_code() {
+        assert_eq!(run(["rlox", "-e", "var a = 1;"]), 0);
+        assert_eq!(run(["rlox", "-e", "1 / 0;"]), 70);
+    }
+
+    #[test]
+    fn test_check_reports_compile_errors_without_running() {
+        let bad = helper_write_script("check_reports_compile_errors_bad", "print (;");
+        let runtime = helper_write_script("check_reports_compile_errors_runtime", "print -nil;");
+        assert_eq!(run(["rlox", "check", &bad]), 65);
+        assert_eq!(run(["rlox", "check", &runtime]), 0);
+        assert_eq!(run(["rlox", "run", &runtime]), 70);
+        assert_eq!(run(["rlox", &runtime]), 70);
+    }
+
+    #[test]
+    fn test_vm_flag_runs_on_bytecode_vm() {
+        assert_eq!(run(["rlox", "--vm", "-e", "print 1;"]), 0);
+        assert_eq!(run(["rlox", "-e", "1 / 0;", "--vm"]), 70);
+        let script = helper_write_script("vm_flag_runs_on_bytecode_vm", "print -nil;");
+        assert_eq!(run(["rlox", "run", "--vm", &script]), 70);
+    }
+
+    #[test]
+    fn test_no_opt_flag_is_accepted_everywhere() {
+        assert_eq!(run(["rlox", "--no-opt", "-e", "print 1 + 2;"]), 0);
+        assert_eq!(run(["rlox", "-e", "1 / (1 - 1);", "--no-opt", "--vm"]), 70);
+    }
+
+    #[test]
+    fn test_ast_formats() {
+        let script = helper_write_script("ast_formats", "print 1;");
+        assert_eq!(run(["rlox", "ast", "--format", "json", &script]), 0);
+        assert_eq!(run(["rlox", "ast", "--format", "dot", &script]), 0);
+        assert_eq!(
+            run(["rlox", "ast", "--format", "yaml", &script]),
+            EXIT_USAGE
+        );
+    }
+
+    #[test]
+    fn test_fmt_rewrites_scripts_and_checks_them() {
+        let script = helper_write_script("fmt_rewrites_scripts", "print  1+2 ; // sum\n");
+        assert_eq!(run(["rlox", "fmt", "--check", &script]), EXIT_UNFORMATTED);
+        assert_eq!(run(["rlox", "fmt", "--indent-width", "2", &script]), 0);
+        assert_eq!(
+            std::fs::read_to_string(&script).unwrap(),
+            "print 1 + 2; // sum\n"
+        );
+        assert_eq!(run(["rlox", "fmt", "--check", &script]), 0);
+
+        let bad = helper_write_script("fmt_rewrites_scripts_bad", "print (;");
+        assert_eq!(run(["rlox", "fmt", &bad]), 65);
+    }
+
+    #[test]
+    fn test_missing_file_exits_with_66() {
+        assert_eq!(run(["rlox", "tokens", "/no/such/file.lox"]), EXIT_NO_INPUT);
+    }
+}
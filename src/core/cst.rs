@@ -0,0 +1,701 @@
+use std::{fmt::Write, iter::Peekable, vec::IntoIter};
+
+use super::{span::Span, token::Token, token_type::TokenType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntaxKind {
+    Program,
+    ClassDecl,
+    /// A `fun` declaration or a method, which has no `fun` keyword.
+    Function,
+    ParamList,
+    VarDecl,
+    ExprStmt,
+    PrintStmt,
+    ReturnStmt,
+    IfStmt,
+    WhileStmt,
+    ForStmt,
+    Block,
+    Assign,
+    Binary,
+    Logical,
+    Ternary,
+    Unary,
+    Call,
+    ArgList,
+    Get,
+    Grouping,
+    Literal,
+    Variable,
+    This,
+    Super,
+    /// Tokens that do not fit the grammar where they appear. It is empty
+    /// where an expression or token is missing.
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(Token),
+}
+
+/// A node of the concrete syntax tree. Unlike `Stmt` and `Expr` it keeps
+/// every token, including punctuation, and the tokens keep their trivia, so
+/// the tree can be turned back into the exact source it was built from.
+#[derive(Debug, Clone)]
+pub struct SyntaxNode {
+    pub kind: SyntaxKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    fn new(kind: SyntaxKind, children: Vec<SyntaxElement>) -> SyntaxNode {
+        SyntaxNode { kind, children }
+    }
+
+    /// The source text of the node, trivia included. For a program this is
+    /// the whole input, byte for byte.
+    pub fn text(&self) -> String {
+        self.tokens().map(Token::full_text).collect()
+    }
+
+    /// The node's tokens in source order.
+    pub fn tokens(&self) -> impl Iterator<Item = &Token> {
+        let mut stack = vec![self.children.iter()];
+        std::iter::from_fn(move || loop {
+            let children = stack.last_mut()?;
+            match children.next() {
+                Some(SyntaxElement::Token(token)) => return Some(token),
+                Some(SyntaxElement::Node(node)) => stack.push(node.children.iter()),
+                None => {
+                    stack.pop();
+                }
+            }
+        })
+    }
+
+    /// The span from the node's first token to its last, without trivia.
+    /// Nodes without tokens have none.
+    pub fn span(&self) -> Option<Span> {
+        let first = self.tokens().next()?;
+        let last = self.tokens().last()?;
+        Some(first.span.to(last.span))
+    }
+
+    /// Lists the node and its descendants, one per line and indented by
+    /// depth, with tokens shown by type and lexeme.
+    pub fn dump(&self) -> String {
+        let mut result = String::new();
+        self.dump_into(&mut result, 0);
+        result
+    }
+
+    fn dump_into(&self, result: &mut String, depth: usize) {
+        let _ = writeln!(result, "{}{:?}", "  ".repeat(depth), self.kind);
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.dump_into(result, depth + 1),
+                SyntaxElement::Token(token) => {
+                    let _ = writeln!(
+                        result,
+                        "{}{} {:?}",
+                        "  ".repeat(depth + 1),
+                        token.token_type,
+                        token.lexeme
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Builds a concrete syntax tree from the scanner's tokens, following the
+/// same grammar as `Parser`. It never fails: tokens that do not fit are
+/// wrapped in `Error` nodes and missing ones are marked by empty `Error`
+/// nodes, so every token ends up in the tree exactly once.
+pub struct CstBuilder {
+    tokens: Peekable<IntoIter<Token>>,
+}
+
+impl CstBuilder {
+    pub fn new(tokens: Vec<Token>) -> CstBuilder {
+        CstBuilder {
+            tokens: tokens.into_iter().peekable(),
+        }
+    }
+
+    pub fn build(mut self) -> SyntaxNode {
+        let mut children = Vec::new();
+        while !self.is_at_end() {
+            children.push(self.declaration());
+        }
+        children.extend(self.tokens.map(SyntaxElement::Token));
+        SyntaxNode::new(SyntaxKind::Program, children)
+    }
+
+    fn declaration(&mut self) -> SyntaxElement {
+        match self.peek() {
+            TokenType::Class => self.class_declaration(),
+            TokenType::Fun => {
+                let mut children = Vec::new();
+                self.bump(&mut children);
+                self.function(children)
+            }
+            TokenType::Var => self.var_declaration(),
+            _ => self.statement(),
+        }
+    }
+
+    fn class_declaration(&mut self) -> SyntaxElement {
+        let mut children = Vec::new();
+        self.bump(&mut children);
+        self.expect(TokenType::Identifier, &mut children);
+        if self.eat(TokenType::Less, &mut children) {
+            if self.check(TokenType::Identifier) {
+                children.push(self.leaf(SyntaxKind::Variable));
+            } else {
+                children.push(missing());
+            }
+        }
+        if self.expect(TokenType::LeftBrace, &mut children) {
+            while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+                if self.check(TokenType::Identifier) {
+                    children.push(self.function(Vec::new()));
+                } else {
+                    children.push(self.leaf(SyntaxKind::Error));
+                }
+            }
+            self.expect(TokenType::RightBrace, &mut children);
+        }
+        node(SyntaxKind::ClassDecl, children)
+    }
+
+    /// Finishes a function after its `fun` keyword, if any, is in
+    /// `children`.
+    fn function(&mut self, mut children: Vec<SyntaxElement>) -> SyntaxElement {
+        self.expect(TokenType::Identifier, &mut children);
+        if self.check(TokenType::LeftParen) {
+            let mut params = Vec::new();
+            self.bump(&mut params);
+            if !self.check(TokenType::RightParen) {
+                loop {
+                    self.expect(TokenType::Identifier, &mut params);
+                    if !self.eat(TokenType::Comma, &mut params) {
+                        break;
+                    }
+                }
+            }
+            self.expect(TokenType::RightParen, &mut params);
+            children.push(node(SyntaxKind::ParamList, params));
+        } else {
+            children.push(missing());
+        }
+        if self.check(TokenType::LeftBrace) {
+            children.push(self.block());
+        } else {
+            children.push(missing());
+        }
+        node(SyntaxKind::Function, children)
+    }
+
+    fn var_declaration(&mut self) -> SyntaxElement {
+        let mut children = Vec::new();
+        self.bump(&mut children);
+        self.expect(TokenType::Identifier, &mut children);
+        if self.eat(TokenType::Equal, &mut children) {
+            children.push(self.expression());
+        }
+        self.expect(TokenType::Semicolon, &mut children);
+        node(SyntaxKind::VarDecl, children)
+    }
+
+    fn statement(&mut self) -> SyntaxElement {
+        let kind = match self.peek() {
+            TokenType::For => return self.for_statement(),
+            TokenType::If => return self.if_statement(),
+            TokenType::While => return self.while_statement(),
+            TokenType::LeftBrace => return self.block(),
+            TokenType::Print => SyntaxKind::PrintStmt,
+            TokenType::Return => SyntaxKind::ReturnStmt,
+            // A token that cannot start a statement would otherwise never
+            // be consumed.
+            TokenType::RightBrace | TokenType::RightParen | TokenType::Semicolon => {
+                return self.leaf(SyntaxKind::Error)
+            }
+            _ => SyntaxKind::ExprStmt,
+        };
+
+        let mut children = Vec::new();
+        if kind != SyntaxKind::ExprStmt {
+            self.bump(&mut children);
+        }
+        if !(kind == SyntaxKind::ReturnStmt && self.check(TokenType::Semicolon)) {
+            children.push(self.expression());
+        }
+        self.expect(TokenType::Semicolon, &mut children);
+        node(kind, children)
+    }
+
+    fn for_statement(&mut self) -> SyntaxElement {
+        let mut children = Vec::new();
+        self.bump(&mut children);
+        self.expect(TokenType::LeftParen, &mut children);
+        match self.peek() {
+            TokenType::Semicolon => self.bump(&mut children),
+            TokenType::Var => children.push(self.var_declaration()),
+            _ => {
+                let mut initializer = vec![self.expression()];
+                self.expect(TokenType::Semicolon, &mut initializer);
+                children.push(node(SyntaxKind::ExprStmt, initializer));
+            }
+        }
+        if !self.check(TokenType::Semicolon) {
+            children.push(self.expression());
+        }
+        self.expect(TokenType::Semicolon, &mut children);
+        if !self.check(TokenType::RightParen) {
+            children.push(self.expression());
+        }
+        self.expect(TokenType::RightParen, &mut children);
+        children.push(self.statement());
+        node(SyntaxKind::ForStmt, children)
+    }
+
+    fn if_statement(&mut self) -> SyntaxElement {
+        let mut children = self.condition();
+        children.push(self.statement());
+        if self.eat(TokenType::Else, &mut children) {
+            children.push(self.statement());
+        }
+        node(SyntaxKind::IfStmt, children)
+    }
+
+    fn while_statement(&mut self) -> SyntaxElement {
+        let mut children = self.condition();
+        children.push(self.statement());
+        node(SyntaxKind::WhileStmt, children)
+    }
+
+    /// The keyword and parenthesized condition of an `if` or `while`.
+    fn condition(&mut self) -> Vec<SyntaxElement> {
+        let mut children = Vec::new();
+        self.bump(&mut children);
+        self.expect(TokenType::LeftParen, &mut children);
+        children.push(self.expression());
+        self.expect(TokenType::RightParen, &mut children);
+        children
+    }
+
+    fn block(&mut self) -> SyntaxElement {
+        let mut children = Vec::new();
+        self.bump(&mut children);
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            children.push(self.declaration());
+        }
+        self.expect(TokenType::RightBrace, &mut children);
+        node(SyntaxKind::Block, children)
+    }
+
+    fn expression(&mut self) -> SyntaxElement {
+        let target = self.comma();
+        self.finish_assignment(target, Self::expression)
+    }
+
+    // Call arguments are separated by commas, so an argument is parsed below
+    // the comma operator.
+    fn argument(&mut self) -> SyntaxElement {
+        let target = self.ternary();
+        self.finish_assignment(target, Self::argument)
+    }
+
+    fn finish_assignment(
+        &mut self,
+        target: SyntaxElement,
+        value: fn(&mut Self) -> SyntaxElement,
+    ) -> SyntaxElement {
+        if !self.check(TokenType::Equal) {
+            return target;
+        }
+        let valid = matches!(
+            &target,
+            SyntaxElement::Node(SyntaxNode {
+                kind: SyntaxKind::Variable | SyntaxKind::Get,
+                ..
+            })
+        );
+        // Like `Parser`, keep going after an invalid target, but mark it.
+        let mut children = vec![if valid {
+            target
+        } else {
+            node(SyntaxKind::Error, vec![target])
+        }];
+        self.bump(&mut children);
+        children.push(value(self));
+        node(SyntaxKind::Assign, children)
+    }
+
+    fn comma(&mut self) -> SyntaxElement {
+        self.binary(SyntaxKind::Binary, &[TokenType::Comma], Self::ternary)
+    }
+
+    fn ternary(&mut self) -> SyntaxElement {
+        let mut expr = self.or();
+        while self.check(TokenType::Question) {
+            let mut children = vec![expr];
+            self.bump(&mut children);
+            children.push(self.expression());
+            self.expect(TokenType::Colon, &mut children);
+            children.push(self.ternary());
+            expr = node(SyntaxKind::Ternary, children);
+        }
+        expr
+    }
+
+    fn or(&mut self) -> SyntaxElement {
+        self.binary(SyntaxKind::Logical, &[TokenType::Or], Self::and)
+    }
+
+    fn and(&mut self) -> SyntaxElement {
+        self.binary(SyntaxKind::Logical, &[TokenType::And], Self::equality)
+    }
+
+    fn equality(&mut self) -> SyntaxElement {
+        self.binary(
+            SyntaxKind::Binary,
+            &[TokenType::BangEqual, TokenType::EqualEqual],
+            Self::comparison,
+        )
+    }
+
+    fn comparison(&mut self) -> SyntaxElement {
+        self.binary(
+            SyntaxKind::Binary,
+            &[
+                TokenType::Greater,
+                TokenType::GreaterEqual,
+                TokenType::Less,
+                TokenType::LessEqual,
+            ],
+            Self::term,
+        )
+    }
+
+    fn term(&mut self) -> SyntaxElement {
+        self.binary(
+            SyntaxKind::Binary,
+            &[TokenType::Minus, TokenType::Plus],
+            Self::factor,
+        )
+    }
+
+    fn factor(&mut self) -> SyntaxElement {
+        self.binary(
+            SyntaxKind::Binary,
+            &[TokenType::Slash, TokenType::Star],
+            Self::unary,
+        )
+    }
+
+    /// Parses a left-associative chain of `operand`s joined by `operators`.
+    fn binary(
+        &mut self,
+        kind: SyntaxKind,
+        operators: &[TokenType],
+        operand: fn(&mut Self) -> SyntaxElement,
+    ) -> SyntaxElement {
+        let mut expr = operand(self);
+        while operators.contains(&self.peek()) {
+            let mut children = vec![expr];
+            self.bump(&mut children);
+            children.push(operand(self));
+            expr = node(kind, children);
+        }
+        expr
+    }
+
+    fn unary(&mut self) -> SyntaxElement {
+        if !matches!(self.peek(), TokenType::Bang | TokenType::Minus) {
+            return self.call();
+        }
+        let mut children = Vec::new();
+        self.bump(&mut children);
+        children.push(self.unary());
+        node(SyntaxKind::Unary, children)
+    }
+
+    fn call(&mut self) -> SyntaxElement {
+        let mut expr = self.primary();
+        loop {
+            let mut children = vec![expr];
+            match self.peek() {
+                TokenType::LeftParen => {
+                    let mut arguments = Vec::new();
+                    self.bump(&mut arguments);
+                    if !self.check(TokenType::RightParen) {
+                        loop {
+                            arguments.push(self.argument());
+                            if !self.eat(TokenType::Comma, &mut arguments) {
+                                break;
+                            }
+                        }
+                    }
+                    self.expect(TokenType::RightParen, &mut arguments);
+                    children.push(node(SyntaxKind::ArgList, arguments));
+                    expr = node(SyntaxKind::Call, children);
+                }
+                TokenType::Dot => {
+                    self.bump(&mut children);
+                    self.expect(TokenType::Identifier, &mut children);
+                    expr = node(SyntaxKind::Get, children);
+                }
+                _ => return children.pop().unwrap(),
+            }
+        }
+    }
+
+    fn primary(&mut self) -> SyntaxElement {
+        match self.peek() {
+            TokenType::False
+            | TokenType::True
+            | TokenType::Nil
+            | TokenType::Number
+            | TokenType::String => self.leaf(SyntaxKind::Literal),
+            TokenType::Identifier => self.leaf(SyntaxKind::Variable),
+            TokenType::This => self.leaf(SyntaxKind::This),
+            TokenType::Super => {
+                let mut children = Vec::new();
+                self.bump(&mut children);
+                self.expect(TokenType::Dot, &mut children);
+                self.expect(TokenType::Identifier, &mut children);
+                node(SyntaxKind::Super, children)
+            }
+            TokenType::LeftParen => {
+                let mut children = Vec::new();
+                self.bump(&mut children);
+                children.push(self.expression());
+                self.expect(TokenType::RightParen, &mut children);
+                node(SyntaxKind::Grouping, children)
+            }
+            // Closing tokens are left for the construct they close.
+            TokenType::RightParen
+            | TokenType::RightBrace
+            | TokenType::Semicolon
+            | TokenType::EOF => missing(),
+            _ => self.leaf(SyntaxKind::Error),
+        }
+    }
+
+    /// A node holding just the next token.
+    fn leaf(&mut self, kind: SyntaxKind) -> SyntaxElement {
+        let mut children = Vec::new();
+        self.bump(&mut children);
+        node(kind, children)
+    }
+
+    fn peek(&mut self) -> TokenType {
+        self.tokens
+            .peek()
+            .map_or(TokenType::EOF, |token| token.token_type)
+    }
+
+    fn check(&mut self, token_type: TokenType) -> bool {
+        self.peek() == token_type
+    }
+
+    fn is_at_end(&mut self) -> bool {
+        self.check(TokenType::EOF)
+    }
+
+    /// Moves the next token into `children`. The end of input is never
+    /// consumed before `build` adds it to the program.
+    fn bump(&mut self, children: &mut Vec<SyntaxElement>) {
+        if self.is_at_end() {
+            return;
+        }
+        if let Some(token) = self.tokens.next() {
+            children.push(SyntaxElement::Token(token));
+        }
+    }
+
+    /// Consumes the next token if it has type `token_type`.
+    fn eat(&mut self, token_type: TokenType, children: &mut Vec<SyntaxElement>) -> bool {
+        let matched = self.check(token_type);
+        if matched {
+            self.bump(children);
+        }
+        matched
+    }
+
+    /// Consumes the next token if it has type `token_type`, marking it
+    /// missing otherwise.
+    fn expect(&mut self, token_type: TokenType, children: &mut Vec<SyntaxElement>) -> bool {
+        let matched = self.eat(token_type, children);
+        if !matched {
+            children.push(missing());
+        }
+        matched
+    }
+}
+
+fn node(kind: SyntaxKind, children: Vec<SyntaxElement>) -> SyntaxElement {
+    SyntaxElement::Node(SyntaxNode::new(kind, children))
+}
+
+/// An empty `Error` node standing in for a missing token or expression.
+fn missing() -> SyntaxElement {
+    node(SyntaxKind::Error, Vec::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{error_reporter::ErrorReporter, parser::Parser, scanner::Scanner};
+
+    fn helper_build(source: &str) -> SyntaxNode {
+        let mut error_reporter = ErrorReporter::quiet();
+        let mut scanner = Scanner::new(source.to_string(), &mut error_reporter);
+        scanner.scan_tokens();
+        CstBuilder::new(scanner.tokens).build()
+    }
+
+    #[test]
+    fn test_text_reproduces_the_source() {
+        for source in [
+            "",
+            "  // only a comment\n",
+            "var a = 1; // one\r\n\n/* two\n */ print a +\t2 ;  \n",
+            "fun f(x, y) { return x ? y : (x, y); } class B < A { m() { super.m(); } }",
+            "for (var i = 0; i < 3; i = i + 1) if (i) print \"é\"; else { while (i) i = nil; }",
+            // Scan and syntax errors still round-trip.
+            "print (; } ) @ var = ; class { 1 } \"unterminated",
+            "print 1 /* unterminated",
+        ] {
+            assert_eq!(helper_build(source).text(), source);
+        }
+    }
+
+    #[test]
+    fn test_nodes_follow_the_grammar() {
+        let program = helper_build("x = a, b ? 1 : f(c = 2).d; // done");
+        assert_eq!(
+            program.dump(),
+            "Program
+  ExprStmt
+    Assign
+      Variable
+        Identifier \"x\"
+      Equal \"=\"
+      Binary
+        Variable
+          Identifier \"a\"
+        Comma \",\"
+        Ternary
+          Variable
+            Identifier \"b\"
+          Question \"?\"
+          Literal
+            Number \"1\"
+          Colon \":\"
+          Get
+            Call
+              Variable
+                Identifier \"f\"
+              ArgList
+                LeftParen \"(\"
+                Assign
+                  Variable
+                    Identifier \"c\"
+                  Equal \"=\"
+                  Literal
+                    Number \"2\"
+                RightParen \")\"
+            Dot \".\"
+            Identifier \"d\"
+    Semicolon \";\"
+  EOF \"\"
+"
+        );
+    }
+
+    #[test]
+    fn test_malformed_code_goes_in_error_nodes() {
+        let program = helper_build("print ; }");
+        let SyntaxElement::Node(print) = &program.children[0] else {
+            panic!("Expected a node");
+        };
+        assert_eq!(print.kind, SyntaxKind::PrintStmt);
+        assert!(matches!(
+            &print.children[1],
+            SyntaxElement::Node(SyntaxNode { kind: SyntaxKind::Error, children }) if children.is_empty()
+        ));
+        let SyntaxElement::Node(stray) = &program.children[1] else {
+            panic!("Expected a node");
+        };
+        assert_eq!(stray.kind, SyntaxKind::Error);
+        assert_eq!(stray.text(), "}");
+        assert_eq!(stray.span().map(|span| span.column), Some(9));
+    }
+
+    fn helper_has_error(node: &SyntaxNode) -> bool {
+        node.kind == SyntaxKind::Error
+            || node.children.iter().any(|child| match child {
+                SyntaxElement::Node(node) => helper_has_error(node),
+                SyntaxElement::Token(_) => false,
+            })
+    }
+
+    #[test]
+    fn test_accepts_and_rejects_the_same_inputs_as_the_parser() {
+        let corpus = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/lox_src"))
+            .unwrap()
+            .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap());
+        let snippets = [
+            "var a = 1; var b;",
+            "fun f(x, y) { return x ? y : (x, y); } class B < A { m() { super.m(); } }",
+            "for (var i = 0; i < 3; i = i + 1) if (i) print i; else { while (i) i = nil; }",
+            "for (;;) {} for (a = 1; a; ) {} for (a; a;) print a;",
+            "x = a, b ? 1 : f(c = 2).d; a.b.c = this.d; !-a or b and c != d;",
+            "return; return 1; print f()(1)(2, 3);",
+            "print (;",
+            "print ; }",
+            "1 = 2;",
+            "a + b = c;",
+            "(a) = 1;",
+            "var = 1;",
+            "var a = 1",
+            "fun (x) {}",
+            "fun f(x,) {}",
+            "class { }",
+            "class A < { }",
+            "class A { var a; }",
+            "if a print 1;",
+            "while (true print 1;",
+            "for (var i = 0 i < 1;) {}",
+            "{ print 1;",
+            "print 1 }",
+            "super;",
+            "super.;",
+            "a ? b;",
+            "f(1, 2;",
+            "a.1;",
+            "a.b = 1; f().c = 2; class A < B {} fun g() {}",
+            "f() = 1;",
+            "print \"unterminated",
+            "print 1 @ 2;",
+        ];
+        for source in corpus.chain(snippets.iter().map(|source| source.to_string())) {
+            let mut error_reporter = ErrorReporter::quiet();
+            let mut scanner = Scanner::new(source.clone(), &mut error_reporter);
+            scanner.scan_tokens();
+            let tokens = scanner.tokens;
+            let cst_accepts = !helper_has_error(&CstBuilder::new(tokens.clone()).build());
+            // The parser leaves scan errors to the scanner's own reports.
+            let scanned = !error_reporter.had_error;
+            let parser_accepts =
+                scanned && Parser::new(tokens, &mut error_reporter).parse().is_ok();
+            assert_eq!(cst_accepts, parser_accepts, "{source:?}");
+        }
+    }
+}
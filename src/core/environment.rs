@@ -0,0 +1,109 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use super::{
+    error::{Result, RuntimeError, RuntimeErrorKind},
+    expr::Value,
+    token::Token,
+};
+
+pub struct Environment {
+    values: HashMap<String, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment {
+            values: HashMap::new(),
+            enclosing: None,
+        }
+    }
+
+    pub fn new_enclosing(enclosing: Rc<RefCell<Environment>>) -> Environment {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    pub fn define(&mut self, name: String, value: Value) {
+        self.values.insert(name, value);
+    }
+
+    /// The variables defined in this environment, ignoring enclosing ones.
+    pub fn bindings(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.values.iter()
+    }
+
+    /// Looks up `name` in this environment only, ignoring enclosing ones.
+    pub fn get_here(&self, name: &str) -> Option<Value> {
+        self.values.get(name).cloned()
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Value> {
+        match self.values.get(&name.lexeme) {
+            Some(value) => Ok(value.clone()),
+            None => match &self.enclosing {
+                Some(enclosing) => enclosing.borrow().get(name),
+                None => Err(RuntimeError::new(
+                    RuntimeErrorKind::UndefinedVariable,
+                    name,
+                    format!("Undefined variable '{}'.", name.lexeme),
+                )),
+            },
+        }
+    }
+
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<()> {
+        match self.values.get_mut(&name.lexeme) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            }
+            None => match &self.enclosing {
+                Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+                None => Err(RuntimeError::new(
+                    RuntimeErrorKind::UndefinedVariable,
+                    name,
+                    format!("Undefined variable '{}'.", name.lexeme),
+                )),
+            },
+        }
+    }
+
+    /// Walks `distance` environments up the chain starting at `environment`.
+    pub fn ancestor(
+        environment: &Rc<RefCell<Environment>>,
+        distance: usize,
+    ) -> Rc<RefCell<Environment>> {
+        let mut environment = environment.clone();
+        for _ in 0..distance {
+            let enclosing = environment.borrow().enclosing.clone();
+            match enclosing {
+                Some(enclosing) => environment = enclosing,
+                None => break,
+            }
+        }
+        environment
+    }
+
+    pub fn get_at(&self, distance: usize, name: &Token) -> Result<Value> {
+        match (distance, &self.enclosing) {
+            (0, _) | (_, None) => self.get(name),
+            (_, Some(enclosing)) => enclosing.borrow().get_at(distance - 1, name),
+        }
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: &Token, value: Value) -> Result<()> {
+        match (distance, &self.enclosing) {
+            (0, _) | (_, None) => self.assign(name, value),
+            (_, Some(enclosing)) => enclosing.borrow_mut().assign_at(distance - 1, name, value),
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use std::{error::Error, fmt::Display};
+
+use super::{span::Span, token::Token};
+
+pub type Result<T, E = RuntimeError> = std::result::Result<T, E>;
+
+/// The command was used incorrectly.
+pub const EXIT_USAGE: i32 = 64;
+/// The input program failed to scan, parse or resolve.
+pub const EXIT_DATA_ERROR: i32 = 65;
+/// The input file could not be read.
+pub const EXIT_NO_INPUT: i32 = 66;
+/// The program raised a runtime error.
+pub const EXIT_SOFTWARE: i32 = 70;
+/// An output file could not be written.
+pub const EXIT_CANT_CREATE: i32 = 73;
+/// `rlox fmt --check` found a script that is not formatted.
+pub const EXIT_UNFORMATTED: i32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanErrorKind {
+    UnexpectedCharacter,
+    UnterminatedString,
+    UnterminatedComment,
+}
+
+/// A lexical error covering the offending characters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanError {
+    pub kind: ScanErrorKind,
+    pub span: Span,
+    pub message: String,
+}
+
+impl ScanError {
+    pub fn new(kind: ScanErrorKind, span: Span, message: &str) -> ScanError {
+        ScanError {
+            kind,
+            span,
+            message: message.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// A specific token, like a `;` or `)`, was missing.
+    ExpectedToken,
+    ExpectedExpression,
+    InvalidAssignmentTarget,
+    TooManyArguments,
+    TooManyParameters,
+}
+
+/// A syntax error found at a specific token by the parser.
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    // Boxed because tokens carry their trivia, and parse results are
+    // returned everywhere in the parser.
+    pub token: Box<Token>,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, token: &Token, message: &str) -> ParseError {
+        ParseError {
+            kind,
+            token: Box::new(token.clone()),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.token.span
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolveErrorKind {
+    ReadInOwnInitializer,
+    DuplicateVariable,
+    ReturnAtTopLevel,
+    ReturnValueFromInitializer,
+    ThisOutsideClass,
+    SuperOutsideClass,
+    SuperWithoutSuperclass,
+    InheritFromSelf,
+}
+
+/// A static error found by the resolver in a program that parsed, located
+/// at the offending name or keyword.
+#[derive(Debug, Clone)]
+pub struct ResolveError {
+    pub kind: ResolveErrorKind,
+    pub span: Span,
+    pub message: String,
+}
+
+impl ResolveError {
+    pub fn new(kind: ResolveErrorKind, token: &Token, message: &str) -> ResolveError {
+        ResolveError {
+            kind,
+            span: token.span,
+            message: message.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeErrorKind {
+    /// An operator was applied to operands of the wrong type.
+    InvalidOperand,
+    DivisionByZero,
+    UndefinedVariable,
+    UndefinedProperty,
+    NotCallable,
+    ArityMismatch,
+    /// A property was read or written on something that isn't an instance.
+    NotAnInstance,
+    SuperclassNotClass,
+    /// Calls nested deeper than `MAX_CALL_DEPTH`.
+    StackOverflow,
+}
+
+/// A function or method call that was active when a runtime error was
+/// raised.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackFrame {
+    pub function: String,
+    /// The closing parenthesis of the call.
+    pub call_site: Span,
+}
+
+/// An error raised while running a program, located at the span of the
+/// token that caused it.
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub kind: RuntimeErrorKind,
+    pub span: Span,
+    pub message: String,
+    /// The calls active when the error was raised, innermost first. Empty for
+    /// errors raised in top-level code.
+    pub trace: Vec<StackFrame>,
+}
+
+impl RuntimeError {
+    pub fn new(kind: RuntimeErrorKind, token: &Token, message: String) -> RuntimeError {
+        RuntimeError {
+            kind,
+            span: token.span,
+            message,
+            trace: Vec::new(),
+        }
+    }
+}
+
+/// A malformed S-expression given to `AstReader`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl ReadError {
+    pub fn new(span: Span, message: &str) -> ReadError {
+        ReadError {
+            span,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Why `Lox::run` failed.
+#[derive(Debug)]
+pub enum LoxError {
+    /// The program had scan, syntax or resolution errors and was not run.
+    Compile {
+        scan_errors: Vec<ScanError>,
+        parse_errors: Vec<ParseError>,
+        resolve_errors: Vec<ResolveError>,
+    },
+    Runtime(RuntimeError),
+}
+
+impl LoxError {
+    /// The process exit status for this error, following jlox and BSD's
+    /// sysexits.h.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            LoxError::Compile { .. } => EXIT_DATA_ERROR,
+            LoxError::Runtime(_) => EXIT_SOFTWARE,
+        }
+    }
+}
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at {}:{}",
+            self.message, self.span.line, self.span.column
+        )
+    }
+}
+
+impl Display for LoxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoxError::Compile {
+                scan_errors,
+                parse_errors,
+                resolve_errors,
+            } => write!(
+                f,
+                "{} compile error(s)",
+                scan_errors.len() + parse_errors.len() + resolve_errors.len()
+            ),
+            LoxError::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for ScanError {}
+
+impl Error for ParseError {}
+
+impl Error for ResolveError {}
+
+impl Error for RuntimeError {}
+
+impl Error for ReadError {}
+
+impl Error for LoxError {}
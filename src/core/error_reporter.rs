@@ -1,28 +1,358 @@
-use super::{token::Token, token_type::TokenType};
+use std::io::IsTerminal;
+
+use super::{error::RuntimeError, span::Span, token::Token, token_type::TokenType};
+
+/// Error codes, one per pipeline stage that can report a diagnostic.
+pub const SCAN_ERROR: &str = "E0001";
+pub const SYNTAX_ERROR: &str = "E0002";
+pub const RESOLVE_ERROR: &str = "E0003";
+pub const RUNTIME_ERROR: &str = "E0004";
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// A single error reported while scanning, parsing, resolving or running a
+/// program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub message: String,
+    pub line: u32,
+    pub span: Option<Span>,
+    /// Short text printed next to the caret, e.g. "at end".
+    pub label: Option<String>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(code: &'static str, message: &str, line: u32) -> Diagnostic {
+        Diagnostic {
+            code,
+            message: message.to_string(),
+            line,
+            span: None,
+            label: None,
+            notes: Vec::new(),
+            help: None,
+        }
+    }
+
+    pub fn with_span(mut self, span: Span) -> Diagnostic {
+        self.line = span.line;
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_label(mut self, label: &str) -> Diagnostic {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Diagnostic {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn with_help(mut self, help: &str) -> Diagnostic {
+        self.help = Some(help.to_string());
+        self
+    }
+}
 
 pub struct ErrorReporter {
     pub had_error: bool,
+    /// Every diagnostic reported since the last `reset`, in order.
+    pub diagnostics: Vec<Diagnostic>,
+    source_name: String,
+    source: String,
+    // Paint diagnostics with ANSI colors, on by default when stdout is a TTY.
+    color: bool,
+    // Collect diagnostics without printing them.
+    quiet: bool,
+    // Index of the first diagnostic whose printing is held back by `hold`.
+    held: Option<usize>,
+}
+
+impl Default for ErrorReporter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ErrorReporter {
     pub fn new() -> ErrorReporter {
-        ErrorReporter { had_error: false }
+        ErrorReporter {
+            had_error: false,
+            diagnostics: Vec::new(),
+            source_name: "<input>".to_string(),
+            source: String::new(),
+            color: std::io::stdout().is_terminal(),
+            quiet: false,
+            held: None,
+        }
+    }
+
+    /// A reporter that only collects diagnostics, for callers that inspect
+    /// or render them on their own.
+    pub fn quiet() -> ErrorReporter {
+        ErrorReporter {
+            quiet: true,
+            ..ErrorReporter::new()
+        }
+    }
+
+    /// Sets the program text that snippets are rendered from.
+    pub fn set_source(&mut self, name: &str, source: &str) {
+        self.source_name = name.to_string();
+        self.source = source.to_string();
+    }
+
+    pub fn set_color(&mut self, color: bool) {
+        self.color = color;
+    }
+
+    /// Forgets previously reported errors, e.g. between REPL lines.
+    pub fn reset(&mut self) {
+        self.had_error = false;
+        self.diagnostics.clear();
+        self.held = self.held.map(|_| 0);
+    }
+
+    /// Holds back printing diagnostics until `release`, so errors found by
+    /// separate passes over the same source print in source order.
+    pub fn hold(&mut self) {
+        self.held.get_or_insert(self.diagnostics.len());
+    }
+
+    /// Sorts the diagnostics reported since `hold` by position and prints
+    /// them.
+    pub fn release(&mut self) {
+        let Some(start) = self.held.take() else {
+            return;
+        };
+        self.diagnostics[start..]
+            .sort_by_key(|diagnostic| (diagnostic.line, diagnostic.span.map(|span| span.start)));
+        if !self.quiet {
+            for diagnostic in &self.diagnostics[start..] {
+                eprint!("{}", self.render(diagnostic));
+            }
+        }
     }
 
-    pub fn error(&mut self, line: u32, message: &String) {
-        self.report(line, &"".to_string(), message);
+    pub fn error(&mut self, line: u32, message: &str) {
+        self.report(Diagnostic::new(SCAN_ERROR, message, line));
     }
 
-    pub fn token_error(&mut self, token: Token, message: &String) {
+    pub fn scan_error(&mut self, span: Span, message: &str) {
+        self.report(Diagnostic::new(SCAN_ERROR, message, span.line).with_span(span));
+    }
+
+    pub fn token_error(&mut self, token: Token, message: &str) {
+        self.report(Self::token_diagnostic(SYNTAX_ERROR, &token, message));
+    }
+
+    pub fn runtime_error(&mut self, error: &RuntimeError) {
+        let mut diagnostic =
+            Diagnostic::new(RUNTIME_ERROR, &error.message, 0).with_span(error.span);
+        for frame in &error.trace {
+            diagnostic = diagnostic.with_note(&format!(
+                "in {}(), called from line {}",
+                frame.function, frame.call_site.line
+            ));
+        }
+        self.report(diagnostic);
+    }
+
+    /// Builds a diagnostic pointing at `token`, labelled "at end" for EOF.
+    pub fn token_diagnostic(code: &'static str, token: &Token, message: &str) -> Diagnostic {
+        let diagnostic = Diagnostic::new(code, message, token.span.line).with_span(token.span);
         if token.token_type == TokenType::EOF {
-            self.report(token.line, &"at end".to_string(), message);
+            diagnostic.with_label("at end")
         } else {
-            self.report(token.line, &token.lexeme, message);
+            diagnostic
         }
     }
 
-    fn report(&mut self, line: u32, place: &String, message: &String) {
-        println!("[line {line}] Error {place}: {message}");
+    pub fn report(&mut self, diagnostic: Diagnostic) {
+        if !self.quiet && self.held.is_none() {
+            eprint!("{}", self.render(&diagnostic));
+        }
+        self.diagnostics.push(diagnostic);
         self.had_error = true;
     }
+
+    /// Renders `diagnostic` in the style of:
+    ///
+    /// ```text
+    /// error[E0002]: Expect ';' after value.
+    ///  --> script.lox:1:8
+    ///   |
+    /// 1 | print 1
+    ///   |        ^ at end
+    /// ```
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let paint = |color: &str, text: &str| {
+            if self.color {
+                format!("{color}{text}{RESET}")
+            } else {
+                text.to_string()
+            }
+        };
+
+        let mut out = format!(
+            "{}{}\n",
+            paint(RED, &format!("error[{}]", diagnostic.code)),
+            paint(BOLD, &format!(": {}", diagnostic.message)),
+        );
+
+        let line_text = self
+            .source
+            .lines()
+            .nth((diagnostic.line as usize).saturating_sub(1))
+            .filter(|_| diagnostic.line > 0);
+        let gutter = " ".repeat(diagnostic.line.to_string().len());
+
+        match diagnostic.span {
+            Some(span) => out.push_str(&format!(
+                "{gutter}{} {}:{}:{}\n",
+                paint(BLUE, "-->"),
+                self.source_name,
+                span.line,
+                span.column
+            )),
+            None => out.push_str(&format!(
+                "{gutter}{} {}:{}\n",
+                paint(BLUE, "-->"),
+                self.source_name,
+                diagnostic.line
+            )),
+        }
+
+        if let Some(line_text) = line_text {
+            let bar = paint(BLUE, "|");
+            out.push_str(&format!("{gutter} {bar}\n"));
+            out.push_str(&format!(
+                "{} {bar} {line_text}\n",
+                paint(BLUE, &diagnostic.line.to_string())
+            ));
+
+            if let Some(span) = diagnostic.span {
+                // Keep tabs so the caret lines up with the source line.
+                let indent: String = line_text
+                    .chars()
+                    .take(span.column.saturating_sub(1) as usize)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                // Spans that run past the end of the line are cut off there.
+                let remaining = line_text.chars().count().saturating_sub(indent.len());
+                let width = self
+                    .source
+                    .get(span.start..span.end())
+                    .map_or(0, |text| text.chars().count())
+                    .min(remaining)
+                    .max(1);
+                let mut marker = "^".repeat(width);
+                if let Some(label) = &diagnostic.label {
+                    marker.push(' ');
+                    marker.push_str(label);
+                }
+                out.push_str(&format!("{gutter} {bar} {indent}{}\n", paint(RED, &marker)));
+            }
+        }
+
+        for note in &diagnostic.notes {
+            out.push_str(&format!("{gutter} {} {note}\n", paint(BLUE, "= note:")));
+        }
+        if let Some(help) = &diagnostic.help {
+            out.push_str(&format!("{gutter} {} {help}\n", paint(CYAN, "= help:")));
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{parser::Parser, scanner::Scanner};
+
+    fn helper_diagnostics(source: &str) -> (ErrorReporter, Vec<Diagnostic>) {
+        let mut error_reporter = ErrorReporter::new();
+        error_reporter.set_color(false);
+        error_reporter.set_source("test.lox", source);
+        let mut scanner = Scanner::new(source.to_string(), &mut error_reporter);
+        scanner.scan_tokens();
+        let tokens = scanner.tokens;
+        let _ = Parser::new(tokens, &mut error_reporter).parse();
+        let diagnostics = error_reporter.diagnostics.clone();
+        (error_reporter, diagnostics)
+    }
+
+    #[test]
+    fn test_diagnostics_are_collected() {
+        let (error_reporter, diagnostics) = helper_diagnostics("var = 2;\nprint (1;\n@");
+        assert!(error_reporter.had_error);
+        let codes: Vec<&str> = diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(codes, vec![SCAN_ERROR, SYNTAX_ERROR, SYNTAX_ERROR]);
+    }
+
+    #[test]
+    fn test_released_diagnostics_are_in_source_order() {
+        let source = "var = 2;\nprint (1;\n@";
+        let mut error_reporter = ErrorReporter::quiet();
+        error_reporter.hold();
+        let mut scanner = Scanner::new(source.to_string(), &mut error_reporter);
+        scanner.scan_tokens();
+        let tokens = scanner.tokens;
+        let _ = Parser::new(tokens, &mut error_reporter).parse();
+        error_reporter.release();
+        let lines: Vec<u32> = error_reporter.diagnostics.iter().map(|d| d.line).collect();
+        assert_eq!(lines, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_render_underlines_span() {
+        let (error_reporter, diagnostics) = helper_diagnostics("var 123 = 1;");
+        assert_eq!(
+            error_reporter.render(&diagnostics[0]),
+            "error[E0002]: Expect variable name.\n \
+             --> test.lox:1:5\n  \
+             |\n\
+             1 | var 123 = 1;\n  \
+             |     ^^^\n"
+        );
+    }
+
+    #[test]
+    fn test_render_labels_end_of_input() {
+        let (error_reporter, diagnostics) = helper_diagnostics("print 1");
+        assert_eq!(
+            error_reporter.render(&diagnostics[0]),
+            "error[E0002]: Expect ';' after value.\n \
+             --> test.lox:1:8\n  \
+             |\n\
+             1 | print 1\n  \
+             |        ^ at end\n"
+        );
+    }
+
+    #[test]
+    fn test_render_notes_and_help() {
+        let mut error_reporter = ErrorReporter::new();
+        error_reporter.set_color(false);
+        let diagnostic = Diagnostic::new(RESOLVE_ERROR, "Oops.", 3)
+            .with_note("a note")
+            .with_help("some help");
+        assert_eq!(
+            error_reporter.render(&diagnostic),
+            "error[E0003]: Oops.\n \
+             --> <input>:3\n  \
+             = note: a note\n  \
+             = help: some help\n"
+        );
+    }
 }
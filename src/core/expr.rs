@@ -1,11 +1,32 @@
-use super::token::Token;
-use anyhow::{anyhow, Result};
+use super::{
+    error::{Result, RuntimeErrorKind},
+    interpreter::Interpreter,
+    lox_class::LoxClass,
+    lox_instance::LoxInstance,
+    span::Span,
+    token::Token,
+};
 use std::{
+    cell::{Cell, RefCell},
     cmp::Ordering,
-    fmt::Display,
+    fmt::{Debug, Display},
     ops::{Add, Div, Mul, Sub},
+    rc::Rc,
 };
 
+pub trait LoxCallable: Display {
+    fn arity(&self) -> usize;
+    /// The name shown for this callable in stack traces.
+    fn name(&self) -> String;
+    fn call(self: Rc<Self>, interpreter: &Interpreter, arguments: Vec<Value>) -> Result<Value>;
+}
+
+impl Debug for dyn LoxCallable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Value {
     Nil,
@@ -13,6 +34,9 @@ pub enum Value {
     False,
     Number(f64),
     String(String),
+    Callable(Rc<dyn LoxCallable>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
 }
 
 impl Display for Value {
@@ -23,61 +47,60 @@ impl Display for Value {
             Value::False => write!(f, "false"),
             Value::Number(value) => write!(f, "{}", value),
             Value::String(ref value) => write!(f, "{}", value),
+            Value::Callable(ref callable) => write!(f, "{}", callable),
+            Value::Class(ref class) => write!(f, "{}", class),
+            Value::Instance(ref instance) => write!(f, "{}", instance.borrow()),
         }
     }
 }
 
 impl Add for Value {
-    type Output = Result<Self>;
+    type Output = Result<Self, RuntimeErrorKind>;
 
-    fn add(self, other: Self) -> Result<Self> {
+    fn add(self, other: Self) -> Self::Output {
         match (&self, &other) {
-            (Value::String(value), _) => {
-                return Ok(Value::String(format!("{}{}", value, other)));
-            }
-            (_, Value::String(value)) => {
-                return Ok(Value::String(format!("{}{}", self, value,)));
-            }
+            (Value::String(value), _) => Ok(Value::String(format!("{}{}", value, other))),
+            (_, Value::String(value)) => Ok(Value::String(format!("{}{}", self, value,))),
             (Value::Number(value), Value::Number(other)) => Ok(Value::Number(value + other)),
-            _ => Err(anyhow!("Applying '+' operator to a non number.")),
+            _ => Err(RuntimeErrorKind::InvalidOperand),
         }
     }
 }
 
 impl Sub for Value {
-    type Output = Result<Self>;
+    type Output = Result<Self, RuntimeErrorKind>;
 
-    fn sub(self, other: Self) -> Result<Self> {
+    fn sub(self, other: Self) -> Self::Output {
         match (self, other) {
             (Value::Number(value), Value::Number(other)) => Ok(Value::Number(value - other)),
-            _ => Err(anyhow!("Applying '-' operator to a non number.")),
+            _ => Err(RuntimeErrorKind::InvalidOperand),
         }
     }
 }
 
 impl Mul for Value {
-    type Output = Result<Self>;
+    type Output = Result<Self, RuntimeErrorKind>;
 
-    fn mul(self, other: Self) -> Result<Self> {
+    fn mul(self, other: Self) -> Self::Output {
         match (self, other) {
             (Value::Number(value), Value::Number(other)) => Ok(Value::Number(value * other)),
-            _ => Err(anyhow!("Applying '*' operator to a non number.")),
+            _ => Err(RuntimeErrorKind::InvalidOperand),
         }
     }
 }
 
 impl Div for Value {
-    type Output = Result<Self>;
+    type Output = Result<Self, RuntimeErrorKind>;
 
-    fn div(self, other: Self) -> Result<Self> {
+    fn div(self, other: Self) -> Self::Output {
         match (self, other) {
             (Value::Number(value), Value::Number(other)) => {
                 if other == 0.0 {
-                    return Err(anyhow!("Division by zero."));
+                    return Err(RuntimeErrorKind::DivisionByZero);
                 }
-                return Ok(Value::Number(value / other));
+                Ok(Value::Number(value / other))
             }
-            _ => return Err(anyhow!("Applying '/' operator to a non number.")),
+            _ => Err(RuntimeErrorKind::InvalidOperand),
         }
     }
 }
@@ -111,6 +134,9 @@ impl PartialEq for Value {
             (Value::False, Value::False) => true,
             (Value::Number(value), Value::Number(other)) => (value - other).abs() < f64::EPSILON,
             (Value::String(value), Value::String(other)) => value == other,
+            (Value::Callable(value), Value::Callable(other)) => Rc::ptr_eq(value, other),
+            (Value::Class(value), Value::Class(other)) => Rc::ptr_eq(value, other),
+            (Value::Instance(value), Value::Instance(other)) => Rc::ptr_eq(value, other),
             _ => false,
         }
     }
@@ -129,53 +155,155 @@ impl Value {
     }
 
     pub fn is_number(&self) -> bool {
-        match self {
-            Value::Number(_) => true,
-            _ => false,
-        }
+        matches!(self, Value::Number(_))
     }
 }
 
+/// How many scopes up from a use its variable's binding lives, as filled in
+/// by the resolver. `None` means the variable is global.
+pub type Depth = Cell<Option<usize>>;
+
+/// An expression node. Every node carries the `Span` of source text it was
+/// parsed from; `Variable` and `This` use the span of their token.
 #[derive(Debug, Clone)]
 pub enum Expr {
-    Literal(Option<Value>),
+    Literal {
+        value: Option<Value>,
+        span: Span,
+    },
     Binary {
         left: Box<Expr>,
         operator: Token,
         right: Box<Expr>,
+        span: Span,
     },
     Grouping {
         expression: Box<Expr>,
+        span: Span,
     },
     Unary {
         operator: Token,
         right: Box<Expr>,
+        span: Span,
     },
     Ternary {
         condition: Box<Expr>,
         then_branch: Box<Expr>,
         else_branch: Box<Expr>,
+        span: Span,
+    },
+    Variable {
+        name: Token,
+        depth: Depth,
+    },
+    Assign {
+        name: Token,
+        value: Box<Expr>,
+        depth: Depth,
+        span: Span,
+    },
+    Logical {
+        left: Box<Expr>,
+        operator: Token,
+        right: Box<Expr>,
+        span: Span,
+    },
+    Call {
+        callee: Box<Expr>,
+        paren: Token,
+        arguments: Vec<Expr>,
+        span: Span,
+    },
+    Get {
+        object: Box<Expr>,
+        name: Token,
+        span: Span,
+    },
+    Set {
+        object: Box<Expr>,
+        name: Token,
+        value: Box<Expr>,
+        span: Span,
+    },
+    This {
+        keyword: Token,
+        depth: Depth,
+    },
+    Super {
+        keyword: Token,
+        method: Token,
+        depth: Depth,
+        span: Span,
     },
-    Variable(Token)
 }
 
 impl Expr {
     pub fn accept<T>(&self, visitor: &dyn Visitor<T>) -> T {
         match self {
-            Expr::Literal(value) => visitor.visit_literal(value),
+            Expr::Literal { value, .. } => visitor.visit_literal(value),
             Expr::Binary {
                 left,
                 operator,
                 right,
+                ..
             } => visitor.visit_binary(left, operator, right),
-            Expr::Grouping { expression } => visitor.visit_grouping(expression),
-            Expr::Unary { operator, right } => visitor.visit_unary(operator, right),
+            Expr::Grouping { expression, .. } => visitor.visit_grouping(expression),
+            Expr::Unary {
+                operator, right, ..
+            } => visitor.visit_unary(operator, right),
             Expr::Ternary {
                 condition,
                 then_branch,
                 else_branch,
+                ..
             } => visitor.visit_ternary(condition, then_branch, else_branch),
-            Expr::Variable(token) => visitor.visit_variable(token)
+            Expr::Variable { name, depth } => visitor.visit_variable(name, depth),
+            Expr::Assign {
+                name, value, depth, ..
+            } => visitor.visit_assign(name, value, depth),
+            Expr::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } => visitor.visit_logical(left, operator, right),
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+                ..
+            } => visitor.visit_call(callee, paren, arguments),
+            Expr::Get { object, name, .. } => visitor.visit_get(object, name),
+            Expr::Set {
+                object,
+                name,
+                value,
+                ..
+            } => visitor.visit_set(object, name, value),
+            Expr::This { keyword, depth } => visitor.visit_this(keyword, depth),
+            Expr::Super {
+                keyword,
+                method,
+                depth,
+                ..
+            } => visitor.visit_super(keyword, method, depth),
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Variable { name: token, .. } | Expr::This { keyword: token, .. } => token.span,
+            Expr::Literal { span, .. }
+            | Expr::Binary { span, .. }
+            | Expr::Grouping { span, .. }
+            | Expr::Unary { span, .. }
+            | Expr::Ternary { span, .. }
+            | Expr::Assign { span, .. }
+            | Expr::Logical { span, .. }
+            | Expr::Call { span, .. }
+            | Expr::Get { span, .. }
+            | Expr::Set { span, .. }
+            | Expr::Super { span, .. } => *span,
         }
     }
 }
@@ -186,5 +314,12 @@ pub trait Visitor<T> {
     fn visit_grouping(&self, expression: &Expr) -> T;
     fn visit_unary(&self, operator: &Token, right: &Expr) -> T;
     fn visit_ternary(&self, condition: &Expr, then_branch: &Expr, else_branch: &Expr) -> T;
-    fn visit_variable(&self, token: &Token) -> T;
+    fn visit_variable(&self, token: &Token, depth: &Depth) -> T;
+    fn visit_assign(&self, name: &Token, value: &Expr, depth: &Depth) -> T;
+    fn visit_logical(&self, left: &Expr, operator: &Token, right: &Expr) -> T;
+    fn visit_call(&self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> T;
+    fn visit_get(&self, object: &Expr, name: &Token) -> T;
+    fn visit_set(&self, object: &Expr, name: &Token, value: &Expr) -> T;
+    fn visit_this(&self, keyword: &Token, depth: &Depth) -> T;
+    fn visit_super(&self, keyword: &Token, method: &Token, depth: &Depth) -> T;
 }
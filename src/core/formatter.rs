@@ -0,0 +1,798 @@
+use super::{
+    expr::{Expr, Value},
+    span::Span,
+    stmt::{ForClauses, FunctionDecl, Stmt},
+    token_type::TokenType,
+};
+
+/// Layout settings for the formatter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Spaces per level of nesting.
+    pub indent_width: usize,
+    /// Expressions longer than this are split over several lines where the
+    /// grammar allows it.
+    pub max_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            indent_width: 4,
+            max_width: 80,
+        }
+    }
+}
+
+/// Prints a parsed program back as consistently laid out Lox source.
+///
+/// Comments never reach the parser, so they are put back by position: a
+/// comment on its own line goes before the statement that follows it, and
+/// one on the same line after a statement stays at the end of that line.
+/// Comments inside an expression are moved before its statement. Single
+/// blank lines between statements are kept.
+pub struct Formatter<'a> {
+    options: FormatOptions,
+    source: &'a str,
+    comments: Vec<Span>,
+    next_comment: usize,
+    output: String,
+    indent: usize,
+    // Byte offset just past the last statement or comment written.
+    last_end: usize,
+    // Whether nothing has been written since a block or body was opened.
+    at_body_start: bool,
+}
+
+/// The parts of a `for` loop, which the parser desugars into a `while`.
+struct ForLoop<'s> {
+    initializer: Option<&'s Stmt>,
+    condition: Option<&'s Expr>,
+    increment: Option<&'s Expr>,
+    body: &'s Stmt,
+}
+
+impl<'a> Formatter<'a> {
+    /// Creates a formatter for the program parsed from `source`, given the
+    /// spans of the comments its scanner skipped.
+    pub fn new(source: &'a str, comments: Vec<Span>, options: FormatOptions) -> Formatter<'a> {
+        Formatter {
+            options,
+            source,
+            comments,
+            next_comment: 0,
+            output: String::new(),
+            indent: 0,
+            last_end: 0,
+            at_body_start: true,
+        }
+    }
+
+    pub fn format(mut self, statements: &[Stmt]) -> String {
+        for statement in statements {
+            self.statement(statement);
+        }
+        self.comments_before(self.source.len());
+        self.output
+    }
+
+    fn statement(&mut self, statement: &Stmt) {
+        let span = statement.span();
+        match statement {
+            Stmt::Expression { .. }
+            | Stmt::Print { .. }
+            | Stmt::Var { .. }
+            | Stmt::Return { .. } => self.comments_before(span.end()),
+            _ => self.comments_before(span.start),
+        }
+        self.blank_line_before(span.start);
+
+        match self.for_loop(statement) {
+            Some(for_loop) => self.for_statement(for_loop),
+            None => self.statement_text(statement),
+        }
+        self.end_line(span.end());
+    }
+
+    /// Writes `statement` starting at the current position. Statements that
+    /// end in a block leave the line open after the closing brace.
+    fn statement_text(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Expression { expression, .. } => {
+                let expression = self.expr(expression, self.indent, self.column(), 1);
+                self.write(&format!("{expression};"));
+            }
+            Stmt::Print { expression, .. } => {
+                let expression = self.expr(expression, self.indent, self.column() + 6, 1);
+                self.write(&format!("print {expression};"));
+            }
+            Stmt::Var {
+                name, initializer, ..
+            } => match initializer {
+                Some(initializer) => {
+                    let prefix = format!("var {} = ", name.lexeme);
+                    let initializer =
+                        self.expr(initializer, self.indent, self.column() + width(&prefix), 1);
+                    self.write(&format!("{prefix}{initializer};"));
+                }
+                None => self.write(&format!("var {};", name.lexeme)),
+            },
+            Stmt::Block { statements, span } => self.block(statements, *span),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => self.if_statement(condition, then_branch, else_branch.as_deref()),
+            Stmt::While {
+                condition, body, ..
+            } => {
+                let condition = self.expr(
+                    condition,
+                    self.indent,
+                    self.column() + 7,
+                    closing_width(body),
+                );
+                self.write(&format!("while ({condition})"));
+                self.body(body);
+            }
+            Stmt::Function(declaration) => self.function("fun ", declaration),
+            Stmt::Return { value, .. } => match value {
+                Some(value) => {
+                    let value = self.expr(value, self.indent, self.column() + 7, 1);
+                    self.write(&format!("return {value};"));
+                }
+                None => self.write("return;"),
+            },
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                body_span,
+                ..
+            } => {
+                self.write(&format!("class {} ", name.lexeme));
+                if let Some(superclass) = superclass {
+                    self.write(&format!("< {} ", self.flat(superclass)));
+                }
+                self.write("{");
+                if methods.is_empty() && !self.has_comment_before(body_span.end()) {
+                    self.write("}");
+                    return;
+                }
+                self.open_body(Some(body_span.start));
+                for method in methods {
+                    self.comments_before(method.span.start);
+                    self.blank_line_before(method.span.start);
+                    self.function("", method);
+                    self.end_line(method.span.end());
+                }
+                self.close_body(body_span.end());
+            }
+        }
+    }
+
+    fn if_statement(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: Option<&Stmt>) {
+        let condition = self.expr(
+            condition,
+            self.indent,
+            self.column() + 4,
+            closing_width(then_branch),
+        );
+        self.write(&format!("if ({condition})"));
+        self.body(then_branch);
+
+        let Some(else_branch) = else_branch else {
+            return;
+        };
+        // A comment after the `}` keeps its line, pushing `else` to the next.
+        if self.trailing_comment(then_branch.span().end()).is_some() {
+            self.end_line(then_branch.span().end());
+        }
+        if self.at_line_start() {
+            self.write("else");
+        } else {
+            self.write(" else");
+        }
+        match else_branch {
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.write(" ");
+                self.if_statement(condition, then_branch, else_branch.as_deref());
+            }
+            _ => self.body(else_branch),
+        }
+    }
+
+    fn for_statement(&mut self, for_loop: ForLoop) {
+        let mut header = match for_loop.initializer {
+            Some(Stmt::Var {
+                name,
+                initializer: Some(initializer),
+                ..
+            }) => format!("var {} = {};", name.lexeme, self.flat(initializer)),
+            Some(Stmt::Var { name, .. }) => format!("var {};", name.lexeme),
+            Some(Stmt::Expression { expression, .. }) => format!("{};", self.flat(expression)),
+            _ => ";".to_string(),
+        };
+        if let Some(condition) = for_loop.condition {
+            header.push(' ');
+            header.push_str(&self.flat(condition));
+        }
+        header.push(';');
+        if let Some(increment) = for_loop.increment {
+            header.push(' ');
+            header.push_str(&self.flat(increment));
+        }
+        self.write(&format!("for ({header})"));
+        self.body(for_loop.body);
+    }
+
+    /// Writes the body of an `if`, `else`, `while` or `for`. Blocks open on
+    /// the same line; any other statement goes on its own line, indented.
+    fn body(&mut self, body: &Stmt) {
+        match body {
+            Stmt::Block { statements, span } if self.for_loop(body).is_none() => {
+                self.write(" ");
+                self.block(statements, *span);
+            }
+            _ => {
+                self.open_body(None);
+                self.statement(body);
+                self.indent -= 1;
+            }
+        }
+    }
+
+    /// Writes `{`, the statements, and `}` for a block whose braces span
+    /// `span`.
+    fn block(&mut self, statements: &[Stmt], span: Span) {
+        self.write("{");
+        if statements.is_empty() && !self.has_comment_before(span.end()) {
+            self.write("}");
+            return;
+        }
+        self.open_body(Some(span.start));
+        for statement in statements {
+            self.statement(statement);
+        }
+        self.close_body(span.end());
+    }
+
+    /// Writes a function or method declaration. `keyword` is `"fun "` for
+    /// functions and empty for methods.
+    fn function(&mut self, keyword: &str, declaration: &FunctionDecl) {
+        let params: Vec<&str> = declaration
+            .params
+            .iter()
+            .map(|param| param.lexeme.as_str())
+            .collect();
+        let header = format!("{keyword}{}(", declaration.name.lexeme);
+        let flat = format!("{header}{}) {{", params.join(", "));
+        if params.is_empty() || self.column() + width(&flat) <= self.options.max_width {
+            self.write(&format!("{header}{})", params.join(", ")));
+        } else {
+            let pad = self.pad(self.indent + 1);
+            let params: Vec<String> = params.iter().map(|param| format!("{pad}{param}")).collect();
+            self.write(&format!(
+                "{header}\n{}\n{})",
+                params.join(",\n"),
+                self.pad(self.indent)
+            ));
+        }
+        self.write(" ");
+        self.block(&declaration.body, declaration.body_span);
+    }
+
+    /// Recognizes the statements the parser builds for a `for` loop: a
+    /// `while` it marked with the loop's clauses, wrapped in a block with
+    /// the initializer if there is one.
+    fn for_loop<'s>(&self, statement: &'s Stmt) -> Option<ForLoop<'s>> {
+        let (initializer, while_loop) = match statement {
+            Stmt::Block { statements, .. } => match statements.as_slice() {
+                [initializer, while_loop @ Stmt::While {
+                    for_clauses:
+                        Some(ForClauses {
+                            initializer: true, ..
+                        }),
+                    ..
+                }] => (Some(initializer), while_loop),
+                _ => return None,
+            },
+            Stmt::While {
+                for_clauses:
+                    Some(ForClauses {
+                        initializer: false, ..
+                    }),
+                ..
+            } => (None, statement),
+            _ => return None,
+        };
+        let Stmt::While {
+            condition,
+            body,
+            for_clauses: Some(clauses),
+            ..
+        } = while_loop
+        else {
+            return None;
+        };
+
+        let condition = clauses.condition.then_some(condition);
+        // The increment runs after the body but is written before it.
+        let (increment, body) = match body.as_ref() {
+            Stmt::Block { statements, .. } if clauses.increment => match statements.as_slice() {
+                [body, Stmt::Expression { expression, .. }] => (Some(expression), body),
+                _ => return None,
+            },
+            body => (None, body),
+        };
+        Some(ForLoop {
+            initializer,
+            condition,
+            increment,
+            body,
+        })
+    }
+
+    /// Renders `expression` starting at `column` on a line indented by
+    /// `indent` levels, followed by `suffix` characters on the same line. If
+    /// it does not fit in the maximum width it is split at call arguments,
+    /// binary operators and ternaries, with each continuation line indented
+    /// one level deeper.
+    fn expr(&self, expression: &Expr, indent: usize, column: usize, suffix: usize) -> String {
+        let flat = self.flat(expression);
+        if column + width(&flat) + suffix <= self.options.max_width {
+            return flat;
+        }
+
+        let inner = indent + 1;
+        let inner_column = inner * self.options.indent_width;
+        let pad = self.pad(inner);
+        match expression {
+            Expr::Call {
+                callee, arguments, ..
+            } if !arguments.is_empty() => {
+                let callee = self.expr(callee, indent, column, 1);
+                let arguments: Vec<String> = arguments
+                    .iter()
+                    .map(|argument| format!("{pad}{}", self.expr(argument, inner, inner_column, 1)))
+                    .collect();
+                format!(
+                    "{callee}(\n{}\n{})",
+                    arguments.join(",\n"),
+                    self.pad(indent)
+                )
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+                ..
+            }
+            | Expr::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } if operator.token_type != TokenType::Comma => {
+                let left = self.expr(left, indent, column, operator.lexeme.len() + 1);
+                let right = self.expr(right, inner, inner_column, suffix);
+                format!("{left} {}\n{pad}{right}", operator.lexeme)
+            }
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => format!(
+                "{}\n{pad}? {}\n{pad}: {}",
+                self.expr(condition, indent, column, 0),
+                self.expr(then_branch, inner, inner_column + 2, 0),
+                self.expr(else_branch, inner, inner_column + 2, suffix)
+            ),
+            Expr::Grouping { expression, .. } => {
+                format!(
+                    "({})",
+                    self.expr(expression, indent, column + 1, suffix + 1)
+                )
+            }
+            Expr::Assign { name, value, .. } => {
+                let prefix = format!("{} = ", name.lexeme);
+                let value = self.expr(value, indent, column + width(&prefix), suffix);
+                format!("{prefix}{value}")
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+                ..
+            } => {
+                let prefix = format!("{}.{} = ", self.flat(object), name.lexeme);
+                let value = self.expr(value, indent, column + width(&prefix), suffix);
+                format!("{prefix}{value}")
+            }
+            _ => flat,
+        }
+    }
+
+    /// Renders `expression` on a single line.
+    fn flat(&self, expression: &Expr) -> String {
+        match expression {
+            Expr::Literal { value, .. } => match value {
+                Some(Value::String(value)) => format!("\"{value}\""),
+                Some(value) => value.to_string(),
+                None => "nil".to_string(),
+            },
+            Expr::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } if operator.token_type == TokenType::Comma => {
+                format!("{}, {}", self.flat(left), self.flat(right))
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+                ..
+            }
+            | Expr::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } => format!(
+                "{} {} {}",
+                self.flat(left),
+                operator.lexeme,
+                self.flat(right)
+            ),
+            Expr::Grouping { expression, .. } => format!("({})", self.flat(expression)),
+            Expr::Unary {
+                operator, right, ..
+            } => {
+                // `- -1` rather than `--1`, which reads like a decrement.
+                let right = self.flat(right);
+                if right.starts_with(&operator.lexeme) {
+                    format!("{} {right}", operator.lexeme)
+                } else {
+                    format!("{}{right}", operator.lexeme)
+                }
+            }
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => format!(
+                "{} ? {} : {}",
+                self.flat(condition),
+                self.flat(then_branch),
+                self.flat(else_branch)
+            ),
+            Expr::Variable { name, .. } => name.lexeme.clone(),
+            Expr::Assign { name, value, .. } => format!("{} = {}", name.lexeme, self.flat(value)),
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                let arguments: Vec<String> = arguments
+                    .iter()
+                    .map(|argument| self.flat(argument))
+                    .collect();
+                format!("{}({})", self.flat(callee), arguments.join(", "))
+            }
+            Expr::Get { object, name, .. } => format!("{}.{}", self.flat(object), name.lexeme),
+            Expr::Set {
+                object,
+                name,
+                value,
+                ..
+            } => format!(
+                "{}.{} = {}",
+                self.flat(object),
+                name.lexeme,
+                self.flat(value)
+            ),
+            Expr::This { .. } => "this".to_string(),
+            Expr::Super { method, .. } => format!("super.{}", method.lexeme),
+        }
+    }
+
+    /// Writes, each on its own line, the comments that start before `offset`
+    /// and have not been written yet.
+    fn comments_before(&mut self, offset: usize) {
+        while let Some(&comment) = self.comments.get(self.next_comment) {
+            if comment.start >= offset {
+                break;
+            }
+            self.next_comment += 1;
+            if !self.at_line_start() {
+                self.newline();
+            }
+            self.blank_line_before(comment.start);
+            self.write(&self.source[comment.start..comment.end()]);
+            self.newline();
+            self.last_end = self.last_end.max(comment.end());
+        }
+    }
+
+    fn has_comment_before(&self, offset: usize) -> bool {
+        self.comments
+            .get(self.next_comment)
+            .is_some_and(|comment| comment.start < offset)
+    }
+
+    /// Ends the current line, first appending a comment that followed `end`
+    /// on the same line in the source.
+    fn end_line(&mut self, end: usize) {
+        if self.at_line_start() {
+            return;
+        }
+        self.last_end = self.last_end.max(end);
+        if let Some(comment) = self.trailing_comment(self.last_end) {
+            self.next_comment += 1;
+            self.write(" ");
+            self.write(&self.source[comment.start..comment.end()]);
+            self.last_end = comment.end();
+        }
+        self.newline();
+    }
+
+    /// The next comment, if it follows `end` on the same line.
+    fn trailing_comment(&self, end: usize) -> Option<Span> {
+        let comment = *self.comments.get(self.next_comment)?;
+        let gap = self.source.get(end..comment.start)?;
+        gap.chars()
+            .all(|c| c == ' ' || c == '\t' || c == '\r')
+            .then_some(comment)
+    }
+
+    /// Keeps one blank line before `offset` if the source had any, except
+    /// at the start of the program or of a block.
+    fn blank_line_before(&mut self, offset: usize) {
+        let blank = self
+            .source
+            .get(self.last_end..offset)
+            .is_some_and(|gap| gap.matches('\n').count() > 1);
+        if blank && !self.at_body_start {
+            self.newline();
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.at_line_start() {
+            self.output.push_str(&self.pad(self.indent));
+        }
+        self.output.push_str(text);
+        self.at_body_start = false;
+    }
+
+    /// Writes the comments left before the `}` that ends at `end`, then the
+    /// brace itself.
+    fn close_body(&mut self, end: usize) {
+        self.comments_before(end - 1);
+        self.indent -= 1;
+        self.write("}");
+        self.last_end = self.last_end.max(end);
+    }
+
+    /// Ends the line that opens a block or body and indents what follows.
+    /// `brace` is the offset of the block's `{`, if it has one. Comments
+    /// before it are moved inside, and the gap they leave is not a blank
+    /// line.
+    fn open_body(&mut self, brace: Option<usize>) {
+        self.newline();
+        self.indent += 1;
+        self.at_body_start = true;
+        if let Some(brace) = brace {
+            self.last_end = self.last_end.max(brace + 1);
+        }
+    }
+
+    fn newline(&mut self) {
+        self.output.push('\n');
+    }
+
+    fn at_line_start(&self) -> bool {
+        self.output.is_empty() || self.output.ends_with('\n')
+    }
+
+    /// The column the next character written will be at.
+    fn column(&self) -> usize {
+        if self.at_line_start() {
+            return self.indent * self.options.indent_width;
+        }
+        let line = match self.output.rfind('\n') {
+            Some(newline) => &self.output[newline + 1..],
+            None => &self.output,
+        };
+        width(line)
+    }
+
+    fn pad(&self, indent: usize) -> String {
+        " ".repeat(indent * self.options.indent_width)
+    }
+}
+
+/// How many characters follow a loop or `if` condition on its line.
+fn closing_width(body: &Stmt) -> usize {
+    match body {
+        Stmt::Block { .. } => ") {".len(),
+        _ => ")".len(),
+    }
+}
+
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{error_reporter::ErrorReporter, parser::Parser, scanner::Scanner};
+
+    fn helper_format(source: &str, options: FormatOptions) -> String {
+        let mut error_reporter = ErrorReporter::new();
+        let mut scanner = Scanner::new(source.to_string(), &mut error_reporter);
+        scanner.scan_tokens();
+        let comments = scanner.comments();
+        let statements = Parser::new(scanner.tokens, &mut error_reporter)
+            .parse()
+            .unwrap();
+        let formatted = Formatter::new(source, comments, options).format(&statements);
+        // Formatting is idempotent.
+        let mut error_reporter = ErrorReporter::new();
+        let mut scanner = Scanner::new(formatted.clone(), &mut error_reporter);
+        scanner.scan_tokens();
+        let comments = scanner.comments();
+        let statements = Parser::new(scanner.tokens, &mut error_reporter)
+            .parse()
+            .unwrap();
+        assert_eq!(
+            Formatter::new(&formatted, comments, options).format(&statements),
+            formatted
+        );
+        formatted
+    }
+
+    #[test]
+    fn test_indents_and_spaces_statements() {
+        assert_eq!(
+            helper_format(
+                "var a=1;fun f(x,y){if(x<y)return -x;else{print \"s\";}}
+                 class B<A{m(){return super.m(this.a,(1,2));}} class C{}",
+                FormatOptions::default()
+            ),
+            "var a = 1;\n\
+             fun f(x, y) {\n    \
+                 if (x < y)\n        \
+                     return -x;\n    \
+                 else {\n        \
+                     print \"s\";\n    \
+                 }\n\
+             }\n\
+             class B < A {\n    \
+                 m() {\n        \
+                     return super.m(this.a, (1, 2));\n    \
+                 }\n\
+             }\n\
+             class C {}\n"
+        );
+    }
+
+    #[test]
+    fn test_restores_for_loops() {
+        assert_eq!(
+            helper_format(
+                "for(var i=0;i<3;i=i+1){print i;} for(;;)print 1; for(i=0;i<1;){}",
+                FormatOptions::default()
+            ),
+            "for (var i = 0; i < 3; i = i + 1) {\n    \
+                 print i;\n\
+             }\n\
+             for (;;)\n    \
+                 print 1;\n\
+             for (i = 0; i < 1;) {}\n"
+        );
+    }
+
+    #[test]
+    fn test_keeps_hand_written_while_loops() {
+        assert_eq!(
+            helper_format(
+                "{ var i = 0; while (i < 3) { print i; i = i + 1; } }",
+                FormatOptions::default()
+            ),
+            "{\n    \
+                 var i = 0;\n    \
+                 while (i < 3) {\n        \
+                     print i;\n        \
+                     i = i + 1;\n    \
+                 }\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_keeps_comments_and_blank_lines() {
+        assert_eq!(
+            helper_format(
+                "// header\nvar a; // trailing\n\n\n/* block */\nif (a) {\n  // inside\n} // end\nelse print 1 + /* mid */ 2;\n{ // empty\n}",
+                FormatOptions::default()
+            ),
+            "// header\n\
+             var a; // trailing\n\
+             \n\
+             /* block */\n\
+             if (a) {\n    \
+                 // inside\n\
+             } // end\n\
+             else\n    \
+                 /* mid */\n    \
+                 print 1 + 2;\n\
+             {\n    \
+                 // empty\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_separates_repeated_unary_operators() {
+        assert_eq!(
+            helper_format(
+                "print - -1; print -(-1); print !!a;",
+                FormatOptions::default()
+            ),
+            "print - -1;\nprint -(-1);\nprint ! !a;\n"
+        );
+    }
+
+    #[test]
+    fn test_moves_comments_before_a_brace_into_the_body() {
+        assert_eq!(
+            helper_format(
+                "while (true) // w\n{\n  print 1;\n}\nfun f() // f\n{\n  print 2;\n}",
+                FormatOptions::default()
+            ),
+            "while (true) {\n    \
+                 // w\n    \
+                 print 1;\n\
+             }\n\
+             fun f() {\n    \
+                 // f\n    \
+                 print 2;\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_wraps_long_lines_at_the_max_width() {
+        let options = FormatOptions {
+            indent_width: 2,
+            max_width: 20,
+        };
+        assert_eq!(
+            helper_format(
+                "print f(first, second); var x = one + two + three; fun g(alpha, beta, gamma) {}",
+                options
+            ),
+            "print f(\n  \
+               first,\n  \
+               second\n\
+             );\n\
+             var x = one + two +\n  \
+               three;\n\
+             fun g(\n  \
+               alpha,\n  \
+               beta,\n  \
+               gamma\n\
+             ) {}\n"
+        );
+    }
+}
@@ -1,32 +1,122 @@
-use anyhow::{anyhow, Result};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use super::{
+    environment::Environment,
+    error::{Result, RuntimeError, RuntimeErrorKind, StackFrame},
     error_reporter::ErrorReporter,
-    expr::{Expr, Value, Visitor as ExprVisitor},
-    stmt::{Stmt, Visitor as StmtVisitor, Void},
+    expr::LoxCallable,
+    expr::{Depth, Expr, Value, Visitor as ExprVisitor},
+    lox_class::LoxClass,
+    lox_function::LoxFunction,
+    lox_instance::LoxInstance,
+    stmt::{FunctionDecl, Stmt, Visitor as StmtVisitor},
     token::Token,
     token_type::TokenType,
 };
 
-pub struct Interpreter;
+/// The deepest calls may nest, counting the top-level script, before a
+/// program fails with a stack overflow. The VM uses the same limit.
+pub const MAX_CALL_DEPTH: usize = 10_000;
+
+/// Native stack for a thread that runs the interpreter. A Lox call takes up
+/// to about 13 KiB of it in debug builds, so `MAX_CALL_DEPTH` calls need
+/// around 128 MiB; this doubles that for headroom.
+pub const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+pub struct Interpreter {
+    pub globals: Rc<RefCell<Environment>>,
+    environment: RefCell<Rc<RefCell<Environment>>>,
+    // Calls currently being executed, outermost first.
+    frames: RefCell<Vec<StackFrame>>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Interpreter {
     pub fn new() -> Self {
-        Interpreter
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        Interpreter {
+            globals: globals.clone(),
+            environment: RefCell::new(globals),
+            frames: RefCell::new(Vec::new()),
+        }
     }
 
-    pub fn interpret(&self, statements: &Vec<Stmt>, error_reporter: &mut ErrorReporter) {
+    /// Runs `statements` in order, stopping at and reporting the first
+    /// runtime error.
+    pub fn interpret(&self, statements: &[Stmt], error_reporter: &mut ErrorReporter) -> Result<()> {
+        self.interpret_statements(statements, false, error_reporter)
+    }
+
+    /// Like `interpret`, but also prints the value of every top-level
+    /// expression statement, as the REPL does.
+    pub fn interpret_echoing(
+        &self,
+        statements: &[Stmt],
+        error_reporter: &mut ErrorReporter,
+    ) -> Result<()> {
+        self.interpret_statements(statements, true, error_reporter)
+    }
+
+    fn interpret_statements(
+        &self,
+        statements: &[Stmt],
+        echo: bool,
+        error_reporter: &mut ErrorReporter,
+    ) -> Result<()> {
         for statement in statements {
-            self.execute(statement)
-                .map_err(|error| error_reporter.error(0, &error.to_string()))
-                .ok();
+            let result = match statement {
+                Stmt::Expression { expression, .. } if echo => self
+                    .evalute(expression)
+                    .map(|value| println!("{}", self.stringify(&value))),
+                _ => self.execute(statement).map(|_| ()),
+            };
+            if let Err(error) = result {
+                error_reporter.runtime_error(&error);
+                return Err(error);
+            }
         }
+        Ok(())
     }
 
-    fn execute(&self, statement: &Stmt) -> Result<Void> {
+    fn look_up_variable(&self, name: &Token, depth: &Depth) -> Result<Value> {
+        match depth.get() {
+            Some(distance) => self.environment.borrow().borrow().get_at(distance, name),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    /// Executes a statement. `Some(value)` means a `return` statement was hit
+    /// and the value is unwinding to the enclosing function call.
+    pub(crate) fn execute(&self, statement: &Stmt) -> Result<Option<Value>> {
         statement.accept(self)
     }
-    fn evalute(&self, expression: &Expr) -> Result<Value> {
+
+    pub fn execute_block(
+        &self,
+        statements: &[Stmt],
+        environment: Environment,
+    ) -> Result<Option<Value>> {
+        let previous = self.environment.replace(Rc::new(RefCell::new(environment)));
+
+        let mut result = Ok(None);
+        for statement in statements {
+            result = self.execute(statement);
+            if !matches!(result, Ok(None)) {
+                break;
+            }
+        }
+
+        self.environment.replace(previous);
+        result
+    }
+
+    /// Evaluates an expression in the current environment.
+    pub fn evalute(&self, expression: &Expr) -> Result<Value> {
         expression.accept(self)
     }
 
@@ -39,46 +129,12 @@ impl Interpreter {
         }
     }
 
-    fn stringify(&self, value: &Value) -> String {
-        match value {
-            Value::Nil => "nil".to_string(),
-            Value::True => "true".to_string(),
-            Value::False => "false".to_string(),
-            Value::Number(value) => value.to_string(),
-            Value::String(value) => value.to_string(),
-        }
-    }
-}
-
-impl ExprVisitor<Result<Value>> for Interpreter {
-    fn visit_literal(&self, value: &Option<Value>) -> Result<Value> {
-        let value = match value {
-            Some(value) => value.clone(),
-            None => Value::Nil,
-        };
-
-        Ok(value)
-    }
-
-    fn visit_unary(&self, operator: &Token, right: &Expr) -> Result<Value> {
-        let right = self.evalute(right)?;
-
-        match operator.token_type {
-            TokenType::Minus => {
-                if let Value::Number(value) = right {
-                    Ok(Value::Number(-value))
-                } else {
-                    Err(anyhow!("Applying '-' operator to a non number."))
-                }
-            }
-            TokenType::Bang => Ok(self.is_truthy(&right)),
-            _ => Ok(right),
-        }
-    }
-
-    fn visit_binary(&self, left: &Expr, operation: &Token, right: &Expr) -> Result<Value> {
-        let left = self.evalute(left)?;
-        let right = self.evalute(right)?;
+    fn apply_binary(
+        &self,
+        left: Value,
+        operation: &Token,
+        right: Value,
+    ) -> Result<Value, RuntimeErrorKind> {
         match operation.token_type {
             TokenType::Minus => left - right,
             TokenType::Plus => left + right,
@@ -86,7 +142,7 @@ impl ExprVisitor<Result<Value>> for Interpreter {
             TokenType::Star => left * right,
             TokenType::Greater => {
                 if !left.is_number() || !right.is_number() {
-                    return Err(anyhow!("Applying '>' operator to a non number."));
+                    return Err(RuntimeErrorKind::InvalidOperand);
                 }
                 let value = if left > right {
                     Value::True
@@ -97,7 +153,7 @@ impl ExprVisitor<Result<Value>> for Interpreter {
             }
             TokenType::GreaterEqual => {
                 if !left.is_number() || !right.is_number() {
-                    return Err(anyhow!("Applying '>' operator to a non number."));
+                    return Err(RuntimeErrorKind::InvalidOperand);
                 }
                 let value = if left >= right {
                     Value::True
@@ -108,7 +164,7 @@ impl ExprVisitor<Result<Value>> for Interpreter {
             }
             TokenType::Less => {
                 if !left.is_number() || !right.is_number() {
-                    return Err(anyhow!("Applying '>' operator to a non number."));
+                    return Err(RuntimeErrorKind::InvalidOperand);
                 }
                 let value = if left < right {
                     Value::True
@@ -119,7 +175,7 @@ impl ExprVisitor<Result<Value>> for Interpreter {
             }
             TokenType::LessEqual => {
                 if !left.is_number() || !right.is_number() {
-                    return Err(anyhow!("Applying '>' operator to a non number."));
+                    return Err(RuntimeErrorKind::InvalidOperand);
                 }
                 let value = if left <= right {
                     Value::True
@@ -144,13 +200,71 @@ impl ExprVisitor<Result<Value>> for Interpreter {
                 };
                 Ok(value)
             }
-            _ => Err(anyhow!(
-                "Invalid binary operation: {}",
-                operation.token_type.to_string()
-            )),
+            // The comma operator evaluates both sides and yields the right one.
+            TokenType::Comma => Ok(right),
+            _ => Err(RuntimeErrorKind::InvalidOperand),
+        }
+    }
+
+    fn stringify(&self, value: &Value) -> String {
+        match value {
+            Value::Nil => "nil".to_string(),
+            Value::True => "true".to_string(),
+            Value::False => "false".to_string(),
+            Value::Number(value) => value.to_string(),
+            Value::String(value) => value.to_string(),
+            Value::Callable(callable) => callable.to_string(),
+            Value::Class(class) => class.to_string(),
+            Value::Instance(instance) => instance.borrow().to_string(),
+        }
+    }
+}
+
+impl ExprVisitor<Result<Value>> for Interpreter {
+    fn visit_literal(&self, value: &Option<Value>) -> Result<Value> {
+        let value = match value {
+            Some(value) => value.clone(),
+            None => Value::Nil,
+        };
+
+        Ok(value)
+    }
+
+    fn visit_unary(&self, operator: &Token, right: &Expr) -> Result<Value> {
+        let right = self.evalute(right)?;
+
+        match operator.token_type {
+            TokenType::Minus => {
+                if let Value::Number(value) = right {
+                    Ok(Value::Number(-value))
+                } else {
+                    Err(RuntimeError::new(
+                        RuntimeErrorKind::InvalidOperand,
+                        operator,
+                        "Applying '-' operator to a non number.".to_string(),
+                    ))
+                }
+            }
+            TokenType::Bang => Ok(match self.is_truthy(&right) {
+                Value::True => Value::False,
+                _ => Value::True,
+            }),
+            _ => Ok(right),
         }
     }
 
+    fn visit_binary(&self, left: &Expr, operation: &Token, right: &Expr) -> Result<Value> {
+        let left = self.evalute(left)?;
+        let right = self.evalute(right)?;
+        self.apply_binary(left, operation, right).map_err(|kind| {
+            let message = match kind {
+                RuntimeErrorKind::DivisionByZero => "Division by zero.".to_string(),
+                _ => format!("Applying '{}' operator to a non number.", operation.lexeme),
+            };
+            RuntimeError::new(kind, operation, message)
+        })
+    }
+
     fn visit_grouping(&self, expression: &Expr) -> Result<Value> {
         self.evalute(expression)
     }
@@ -167,154 +281,854 @@ impl ExprVisitor<Result<Value>> for Interpreter {
             self.evalute(else_branch)
         }
     }
+
+    fn visit_variable(&self, token: &Token, depth: &Depth) -> Result<Value> {
+        self.look_up_variable(token, depth)
+    }
+
+    fn visit_assign(&self, name: &Token, value: &Expr, depth: &Depth) -> Result<Value> {
+        let value = self.evalute(value)?;
+        match depth.get() {
+            Some(distance) => {
+                self.environment
+                    .borrow()
+                    .borrow_mut()
+                    .assign_at(distance, name, value.clone())?
+            }
+            None => self.globals.borrow_mut().assign(name, value.clone())?,
+        }
+        Ok(value)
+    }
+
+    fn visit_logical(&self, left: &Expr, operator: &Token, right: &Expr) -> Result<Value> {
+        let left = self.evalute(left)?;
+        let left_is_truthy = self.is_truthy(&left) == Value::True;
+
+        match operator.token_type {
+            TokenType::Or if left_is_truthy => Ok(left),
+            TokenType::And if !left_is_truthy => Ok(left),
+            _ => self.evalute(right),
+        }
+    }
+
+    fn visit_call(&self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> Result<Value> {
+        let callee = self.evalute(callee)?;
+
+        let mut values = Vec::new();
+        for argument in arguments {
+            values.push(self.evalute(argument)?);
+        }
+
+        let function: Rc<dyn LoxCallable> = match callee {
+            Value::Callable(function) => function,
+            Value::Class(class) => class,
+            _ => {
+                return Err(RuntimeError::new(
+                    RuntimeErrorKind::NotCallable,
+                    paren,
+                    "Can only call functions and classes.".to_string(),
+                ))
+            }
+        };
+
+        if values.len() != function.arity() {
+            return Err(RuntimeError::new(
+                RuntimeErrorKind::ArityMismatch,
+                paren,
+                format!(
+                    "Expected {} arguments but got {}.",
+                    function.arity(),
+                    values.len()
+                ),
+            ));
+        }
+
+        if self.frames.borrow().len() + 1 == MAX_CALL_DEPTH {
+            let mut error = RuntimeError::new(
+                RuntimeErrorKind::StackOverflow,
+                paren,
+                "Stack overflow.".to_string(),
+            );
+            error.trace = self.frames.borrow().iter().rev().cloned().collect();
+            return Err(error);
+        }
+
+        self.frames.borrow_mut().push(StackFrame {
+            function: function.name(),
+            call_site: paren.span,
+        });
+        let result = function.call(self, values).map_err(|mut error| {
+            // The innermost call an error passes through records the trace.
+            if error.trace.is_empty() {
+                error.trace = self.frames.borrow().iter().rev().cloned().collect();
+            }
+            error
+        });
+        self.frames.borrow_mut().pop();
+        result
+    }
+
+    fn visit_get(&self, object: &Expr, name: &Token) -> Result<Value> {
+        match self.evalute(object)? {
+            Value::Instance(instance) => LoxInstance::get(&instance, name),
+            _ => Err(RuntimeError::new(
+                RuntimeErrorKind::NotAnInstance,
+                name,
+                "Only instances have properties.".to_string(),
+            )),
+        }
+    }
+
+    fn visit_set(&self, object: &Expr, name: &Token, value: &Expr) -> Result<Value> {
+        let Value::Instance(instance) = self.evalute(object)? else {
+            return Err(RuntimeError::new(
+                RuntimeErrorKind::NotAnInstance,
+                name,
+                "Only instances have fields.".to_string(),
+            ));
+        };
+
+        let value = self.evalute(value)?;
+        instance.borrow_mut().set(name, value.clone());
+        Ok(value)
+    }
+
+    fn visit_this(&self, keyword: &Token, depth: &Depth) -> Result<Value> {
+        self.look_up_variable(keyword, depth)
+    }
+
+    fn visit_super(&self, keyword: &Token, method: &Token, depth: &Depth) -> Result<Value> {
+        let distance = match depth.get() {
+            Some(distance) => distance,
+            None => {
+                return Err(RuntimeError::new(
+                    RuntimeErrorKind::UndefinedVariable,
+                    keyword,
+                    "Can't use 'super' outside of a class.".to_string(),
+                ))
+            }
+        };
+
+        // `this` is always bound in the scope just inside the one binding `super`.
+        let environment = self.environment.borrow().clone();
+        let superclass = Environment::ancestor(&environment, distance)
+            .borrow()
+            .get_here("super");
+        let object = Environment::ancestor(&environment, distance - 1)
+            .borrow()
+            .get_here("this");
+
+        let (Some(Value::Class(superclass)), Some(object)) = (superclass, object) else {
+            return Err(RuntimeError::new(
+                RuntimeErrorKind::UndefinedVariable,
+                keyword,
+                "Can't use 'super' outside of a class.".to_string(),
+            ));
+        };
+
+        match superclass.find_method(&method.lexeme) {
+            Some(method) => Ok(Value::Callable(Rc::new(method.bind(object)))),
+            None => Err(RuntimeError::new(
+                RuntimeErrorKind::UndefinedProperty,
+                method,
+                format!("Undefined property '{}'.", method.lexeme),
+            )),
+        }
+    }
 }
 
-impl StmtVisitor<Result<Void>> for Interpreter {
-    fn visit_expression(&self, expr: &Expr) -> Result<Void> {
+impl StmtVisitor<Result<Option<Value>>> for Interpreter {
+    fn visit_expression(&self, expr: &Expr) -> Result<Option<Value>> {
         self.evalute(expr)?;
-        Ok(Void)
+        Ok(None)
     }
 
-    fn visit_print(&self, expr: &Expr) -> Result<Void> {
+    fn visit_print(&self, expr: &Expr) -> Result<Option<Value>> {
         let value = self.evalute(expr)?;
         println!("{}", self.stringify(&value));
-        Ok(Void)
+        Ok(None)
+    }
+
+    fn visit_var(&self, name: &Token, initializer: &Option<Expr>) -> Result<Option<Value>> {
+        let value = match initializer {
+            Some(initializer) => self.evalute(initializer)?,
+            None => Value::Nil,
+        };
+
+        self.environment
+            .borrow()
+            .borrow_mut()
+            .define(name.lexeme.clone(), value);
+        Ok(None)
+    }
+
+    fn visit_block(&self, statements: &[Stmt]) -> Result<Option<Value>> {
+        let environment = Environment::new_enclosing(self.environment.borrow().clone());
+        self.execute_block(statements, environment)
+    }
+
+    fn visit_if(
+        &self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: &Option<Box<Stmt>>,
+    ) -> Result<Option<Value>> {
+        if self.is_truthy(&self.evalute(condition)?) == Value::True {
+            self.execute(then_branch)
+        } else if let Some(else_branch) = else_branch {
+            self.execute(else_branch)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn visit_while(&self, condition: &Expr, body: &Stmt) -> Result<Option<Value>> {
+        while self.is_truthy(&self.evalute(condition)?) == Value::True {
+            if let Some(value) = self.execute(body)? {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> Result<Option<Value>> {
+        let function = LoxFunction::new(
+            declaration.clone(),
+            self.environment.borrow().clone(),
+            false,
+        );
+        self.environment.borrow().borrow_mut().define(
+            declaration.name.lexeme.clone(),
+            Value::Callable(Rc::new(function)),
+        );
+        Ok(None)
+    }
+
+    fn visit_return(&self, _keyword: &Token, value: &Option<Expr>) -> Result<Option<Value>> {
+        let value = match value {
+            Some(value) => self.evalute(value)?,
+            None => Value::Nil,
+        };
+        Ok(Some(value))
+    }
+
+    fn visit_class(
+        &self,
+        name: &Token,
+        superclass: &Option<Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) -> Result<Option<Value>> {
+        let superclass = match superclass {
+            Some(superclass) => match self.evalute(superclass)? {
+                Value::Class(class) => Some(class),
+                _ => {
+                    let token = match superclass {
+                        Expr::Variable { name, .. } => name,
+                        _ => name,
+                    };
+                    return Err(RuntimeError::new(
+                        RuntimeErrorKind::SuperclassNotClass,
+                        token,
+                        "Superclass must be a class.".to_string(),
+                    ));
+                }
+            },
+            None => None,
+        };
+
+        let enclosing = self.environment.borrow().clone();
+
+        // Methods of a subclass close over an extra scope that binds `super`.
+        let environment = match &superclass {
+            Some(superclass) => {
+                let mut environment = Environment::new_enclosing(enclosing.clone());
+                environment.define("super".to_string(), Value::Class(superclass.clone()));
+                Rc::new(RefCell::new(environment))
+            }
+            None => enclosing.clone(),
+        };
+
+        let mut class_methods = HashMap::new();
+        for method in methods {
+            let function = LoxFunction::new(
+                method.clone(),
+                environment.clone(),
+                method.name.lexeme == "init",
+            );
+            class_methods.insert(method.name.lexeme.clone(), Rc::new(function));
+        }
+
+        let class = LoxClass::new(name.lexeme.clone(), superclass, class_methods);
+        enclosing
+            .borrow_mut()
+            .define(name.lexeme.clone(), Value::Class(Rc::new(class)));
+        Ok(None)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::test_utils::tests::helper_create_expr_from_string;
+    use crate::core::test_utils::tests::{
+        helper_create_expr_from_string, helper_create_stmts_from_string, helper_resolve_stmts,
+        helper_run_and_eval,
+    };
 
     #[test]
     fn test_number_equal() {
-        let expression = helper_create_expr_from_string("1 == 1;");
-        let interpreter = Interpreter::new();
-        let value = interpreter.evalute(&expression);
-        assert_eq!(value.unwrap(), Value::True);
+        assert_eq!(helper_run_and_eval("", "1 == 1;").unwrap(), Value::True);
     }
 
     #[test]
     fn test_nil_equal() {
-        let expression = helper_create_expr_from_string("nil == nil;");
-        let interpreter = Interpreter::new();
-        let value = interpreter.evalute(&expression);
-        assert_eq!(value.unwrap(), Value::True);
+        assert_eq!(helper_run_and_eval("", "nil == nil;").unwrap(), Value::True);
     }
 
     #[test]
     fn test_bool_equal() {
-        let expression = helper_create_expr_from_string("true == true;");
-        let interpreter = Interpreter::new();
-        let value = interpreter.evalute(&expression);
-        assert_eq!(value.unwrap(), Value::True);
+        assert_eq!(
+            helper_run_and_eval("", "true == true;").unwrap(),
+            Value::True
+        );
     }
 
     #[test]
     fn test_string_equal() {
-        let expression = helper_create_expr_from_string("\"hello\" == \"hello\";");
-        let interpreter = Interpreter::new();
-        let value = interpreter.evalute(&expression);
-        assert_eq!(value.unwrap(), Value::True);
+        assert_eq!(
+            helper_run_and_eval("", "\"hello\" == \"hello\";").unwrap(),
+            Value::True
+        );
     }
 
     #[test]
     fn test_number_comparison() {
-        let expression = helper_create_expr_from_string("1 < 2;");
-        let interpreter = Interpreter::new();
-        let value = interpreter.evalute(&expression);
-        assert_eq!(value.unwrap(), Value::True);
+        assert_eq!(helper_run_and_eval("", "1 < 2;").unwrap(), Value::True);
     }
 
     #[test]
     fn test_unary_minus_on_non_number() {
-        let expression = helper_create_expr_from_string("-\"hello\";");
-        let interpreter = Interpreter::new();
-        let value = interpreter.evalute(&expression);
-        assert!(value.is_err());
+        assert_eq!(
+            helper_run_and_eval("", "-\"hello\";").unwrap_err().kind,
+            RuntimeErrorKind::InvalidOperand
+        );
     }
 
     #[test]
     fn test_add_number_with_non_number() {
-        let expression = helper_create_expr_from_string("1 + \"world\";");
-        let interpreter = Interpreter::new();
-        let value = interpreter.evalute(&expression);
-        assert_eq!(value.unwrap(), Value::String("1world".to_string()));
+        assert_eq!(
+            helper_run_and_eval("", "1 + \"world\";").unwrap(),
+            Value::String("1world".to_string())
+        );
     }
 
     #[test]
     fn test_sub_number_with_non_number() {
-        let expression = helper_create_expr_from_string("1 - \"world\";");
-        let interpreter = Interpreter::new();
-        let value = interpreter.evalute(&expression);
-        assert!(value.is_err());
+        assert_eq!(
+            helper_run_and_eval("", "1 - \"world\";").unwrap_err().kind,
+            RuntimeErrorKind::InvalidOperand
+        );
     }
 
     #[test]
     fn test_mul_number_with_non_number() {
-        let expression = helper_create_expr_from_string("1 * \"world\";");
-        let interpreter = Interpreter::new();
-        let value = interpreter.evalute(&expression);
-        assert!(value.is_err());
+        assert_eq!(
+            helper_run_and_eval("", "1 * \"world\";").unwrap_err().kind,
+            RuntimeErrorKind::InvalidOperand
+        );
     }
 
     #[test]
     fn test_div_number_with_non_number() {
-        let expression = helper_create_expr_from_string("1 / \"world\";");
-        let interpreter = Interpreter::new();
-        let value = interpreter.evalute(&expression);
-        assert!(value.is_err());
+        assert_eq!(
+            helper_run_and_eval("", "1 / \"world\";").unwrap_err().kind,
+            RuntimeErrorKind::InvalidOperand
+        );
     }
 
     #[test]
     fn test_compare_number_with_non_number() {
-        let expression = helper_create_expr_from_string("1 < \"world\";");
-        let interpreter = Interpreter::new();
-        let value = interpreter.evalute(&expression);
-        assert!(value.is_err());
+        assert_eq!(
+            helper_run_and_eval("", "1 < \"world\";").unwrap_err().kind,
+            RuntimeErrorKind::InvalidOperand
+        );
     }
 
     #[test]
     fn test_number_equal_non_number() {
-        let expression = helper_create_expr_from_string("1 == \"world\";");
-        let interpreter = Interpreter::new();
-        let value = interpreter.evalute(&expression);
-        assert_eq!(value.unwrap(), Value::False);
+        assert_eq!(
+            helper_run_and_eval("", "1 == \"world\";").unwrap(),
+            Value::False
+        );
     }
 
     #[test]
     fn test_add_string_with_non_string() {
-        let expression = helper_create_expr_from_string("\"hello\" + 1;");
-        let interpreter = Interpreter::new();
-        let value = interpreter.evalute(&expression);
-        assert_eq!(value.unwrap(), Value::String("hello1".to_string()));
+        assert_eq!(
+            helper_run_and_eval("", "\"hello\" + 1;").unwrap(),
+            Value::String("hello1".to_string())
+        );
     }
 
     #[test]
     fn test_add_non_string_with_string() {
-        let expression = helper_create_expr_from_string("true + \"hello\";");
-        let interpreter = Interpreter::new();
-        let value = interpreter.evalute(&expression);
-        assert_eq!(value.unwrap(), Value::String("truehello".to_string()));
+        assert_eq!(
+            helper_run_and_eval("", "true + \"hello\";").unwrap(),
+            Value::String("truehello".to_string())
+        );
     }
 
     #[test]
     fn test_adding_string_with_non_string_n_times() {
-        let expression = helper_create_expr_from_string("true + \"hello\" + 1 + nil + \"world\";");
-        let interpreter = Interpreter::new();
-        let value = interpreter.evalute(&expression);
         assert_eq!(
-            value.unwrap(),
+            helper_run_and_eval("", "true + \"hello\" + 1 + nil + \"world\";").unwrap(),
             Value::String("truehello1nilworld".to_string())
         );
     }
 
+    #[test]
+    fn test_bang_negates_truthiness() {
+        assert_eq!(helper_run_and_eval("", "!true;").unwrap(), Value::False);
+        assert_eq!(helper_run_and_eval("", "!nil;").unwrap(), Value::True);
+    }
+
     #[test]
     fn test_divide_by_zero_reports_runtime_error() {
-        let expression = helper_create_expr_from_string("1 / 0;");
+        assert_eq!(
+            helper_run_and_eval("", "1 / 0;").unwrap_err().kind,
+            RuntimeErrorKind::DivisionByZero
+        );
+    }
+
+    #[test]
+    fn test_var_declaration_defines_global() {
+        assert_eq!(
+            helper_run_and_eval("var a = 1 + 2;", "a;").unwrap(),
+            Value::Number(3.0)
+        );
+    }
+
+    #[test]
+    fn test_var_declaration_without_initializer_is_nil() {
+        assert_eq!(
+            helper_run_and_eval("var a;", "a == nil;").unwrap(),
+            Value::True
+        );
+    }
+
+    #[test]
+    fn test_undefined_variable_reports_runtime_error() {
+        assert_eq!(
+            helper_run_and_eval("", "a;").unwrap_err().to_string(),
+            "Undefined variable 'a'.".to_string()
+        );
+    }
+
+    #[test]
+    fn test_assignment_is_right_associative() {
+        assert_eq!(
+            helper_run_and_eval("var a; var b; a = b = 3;", "a == 3 ? b : nil;").unwrap(),
+            Value::Number(3.0)
+        );
+    }
+
+    #[test]
+    fn test_assign_undefined_variable_reports_runtime_error() {
+        assert_eq!(
+            helper_run_and_eval("", "a = 1;").unwrap_err().to_string(),
+            "Undefined variable 'a'.".to_string()
+        );
+    }
+
+    #[test]
+    fn test_block_shadows_and_restores_outer_variable() {
+        assert_eq!(
+            helper_run_and_eval("var a = 1; var b = 0; { var a = 2; b = a; }", "a + b;").unwrap(),
+            Value::Number(3.0)
+        );
+    }
+
+    #[test]
+    fn test_block_variable_is_not_visible_outside() {
+        assert_eq!(
+            helper_run_and_eval("{ var a = 1; }", "a;")
+                .unwrap_err()
+                .kind,
+            RuntimeErrorKind::UndefinedVariable
+        );
+    }
+
+    #[test]
+    fn test_block_restores_environment_after_runtime_error() {
+        let statements = helper_create_stmts_from_string("var a = 1; { var a = 2; a / 0; }");
         let interpreter = Interpreter::new();
+        helper_resolve_stmts(&statements);
+        interpreter.execute(&statements[0]).unwrap();
+        assert_eq!(
+            interpreter.execute(&statements[1]).unwrap_err().kind,
+            RuntimeErrorKind::DivisionByZero
+        );
+        let expression = helper_create_expr_from_string("a;");
         let value = interpreter.evalute(&expression);
-        assert!(value.is_err());
+        assert_eq!(value.unwrap(), Value::Number(1.0));
+    }
+
+    #[test]
+    fn test_if_else_takes_matching_branch() {
+        assert_eq!(
+            helper_run_and_eval("var a; if (1 > 2) a = \"then\"; else a = \"else\";", "a;")
+                .unwrap(),
+            Value::String("else".to_string())
+        );
+    }
+
+    #[test]
+    fn test_dangling_else_binds_to_nearest_if() {
+        assert_eq!(
+            helper_run_and_eval(
+                "var a = \"none\"; if (true) if (false) a = \"inner\"; else a = \"nearest\";",
+                "a;"
+            )
+            .unwrap(),
+            Value::String("nearest".to_string())
+        );
+    }
+
+    #[test]
+    fn test_while_loop_runs_until_condition_is_false() {
+        assert_eq!(
+            helper_run_and_eval(
+                "var i = 0; var sum = 0; while (i < 5) { sum = sum + i; i = i + 1; }",
+                "sum;"
+            )
+            .unwrap(),
+            Value::Number(10.0)
+        );
+    }
+
+    #[test]
+    fn test_for_loop_desugars_to_while() {
+        let source = "var product = 1; for (var i = 1; i <= 5; i = i + 1) product = product * i;";
+        assert_eq!(
+            helper_run_and_eval(source, "product;").unwrap(),
+            Value::Number(120.0)
+        );
+        assert_eq!(
+            helper_run_and_eval(source, "i;").unwrap_err().kind,
+            RuntimeErrorKind::UndefinedVariable
+        );
+    }
+
+    #[test]
+    fn test_logical_or_returns_operand_value() {
+        assert_eq!(
+            helper_run_and_eval("", "nil or \"yes\";").unwrap(),
+            Value::String("yes".to_string())
+        );
+    }
+
+    #[test]
+    fn test_logical_and_returns_operand_value() {
+        assert_eq!(
+            helper_run_and_eval("", "1 and 2;").unwrap(),
+            Value::Number(2.0)
+        );
+    }
+
+    #[test]
+    fn test_logical_short_circuits_right_operand() {
+        assert_eq!(
+            helper_run_and_eval("", "(false and 1 / 0) or (\"hi\" or 1 / 0);").unwrap(),
+            Value::String("hi".to_string())
+        );
+    }
+
+    #[test]
+    fn test_function_call_returns_value() {
+        assert_eq!(
+            helper_run_and_eval(
+                "fun add(a, b) { return a + b; } var sum = add(1, 2);",
+                "sum;"
+            )
+            .unwrap(),
+            Value::Number(3.0)
+        );
+    }
+
+    #[test]
+    fn test_function_without_return_yields_nil() {
+        assert_eq!(
+            helper_run_and_eval("fun noop() {} var result = noop();", "result;").unwrap(),
+            Value::Nil
+        );
+    }
+
+    #[test]
+    fn test_recursive_function_with_early_return_from_loop() {
+        let source = "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+            fun first_over(limit) { for (var i = 0; ; i = i + 1) { if (fib(i) > limit) return i; } }
+            var result = first_over(20);";
+        assert_eq!(
+            helper_run_and_eval(source, "result;").unwrap(),
+            Value::Number(8.0)
+        );
+    }
+
+    #[test]
+    fn test_arity_mismatch_reports_runtime_error_at_paren() {
+        let statements = helper_create_stmts_from_string("fun f(a) {}\nf(1, 2);");
+        let interpreter = Interpreter::new();
+        helper_resolve_stmts(&statements);
+        interpreter.execute(&statements[0]).unwrap();
+        let error = interpreter.execute(&statements[1]).unwrap_err();
+        assert_eq!(error.kind, RuntimeErrorKind::ArityMismatch);
+        assert_eq!(error.message, "Expected 1 arguments but got 2.");
+        assert_eq!((error.span.line, error.span.column), (2, 7));
+    }
+
+    #[test]
+    fn test_calling_non_function_reports_runtime_error() {
+        assert_eq!(
+            helper_run_and_eval("", "\"not a function\"();")
+                .unwrap_err()
+                .to_string(),
+            "Can only call functions and classes.".to_string()
+        );
+    }
+
+    #[test]
+    fn test_closure_outlives_declaring_call() {
+        let source = "fun makeCounter() { var i = 0; fun c() { i = i + 1; return i; } return c; }
+            var counter = makeCounter();
+            counter();
+            counter();
+            var result = counter();";
+        assert_eq!(
+            helper_run_and_eval(source, "result;").unwrap(),
+            Value::Number(3.0)
+        );
+    }
+
+    #[test]
+    fn test_closures_from_separate_calls_do_not_share_state() {
+        let source = "fun makeCounter() { var i = 0; fun c() { i = i + 1; return i; } return c; }
+            var first = makeCounter();
+            var second = makeCounter();
+            first();
+            first();
+            var result = second();";
+        assert_eq!(
+            helper_run_and_eval(source, "result;").unwrap(),
+            Value::Number(1.0)
+        );
+    }
+
+    #[test]
+    fn test_closure_callback_captures_argument() {
+        let source = "fun adder(n) { fun add(x) { return x + n; } return add; }
+            fun apply(f, x) { return f(x); }
+            var result = apply(adder(10), 5);";
+        assert_eq!(
+            helper_run_and_eval(source, "result;").unwrap(),
+            Value::Number(15.0)
+        );
+    }
+
+    #[test]
+    fn test_closure_keeps_binding_from_declaration_time() {
+        let source = "var a = \"global\";
+            var first;
+            var second;
+            {
+              fun showA() { return a; }
+              first = showA();
+              var a = \"block\";
+              second = showA();
+            }";
+        assert_eq!(
+            helper_run_and_eval(source, "first == second;").unwrap(),
+            Value::True
+        );
+    }
+
+    #[test]
+    fn test_resolution_survives_moving_the_ast() {
+        let mut statements = helper_create_stmts_from_string(
+            "var a = \"global\";
+             var result;
+             { var a = \"block\"; a = a + \"!\"; result = a; }",
+        );
+        helper_resolve_stmts(&statements);
+        // Move the block's statements to a new allocation after resolving.
+        let Some(Stmt::Block {
+            statements: body,
+            span,
+        }) = statements.pop()
+        else {
+            panic!("expected a block");
+        };
+        let mut moved = Vec::with_capacity(body.len() + 1);
+        moved.extend(body);
+        statements.push(Stmt::Block {
+            statements: moved,
+            span,
+        });
+        let interpreter = Interpreter::new();
+        for statement in &statements {
+            interpreter.execute(statement).unwrap();
+        }
+        let expression = helper_create_expr_from_string("result;");
+        let value = interpreter.evalute(&expression);
+        assert_eq!(value.unwrap(), Value::String("block!".to_string()));
+    }
+
+    #[test]
+    fn test_instance_fields_and_methods() {
+        let source = "class Counter {
+              increment() { this.count = this.count + 1; return this; }
+            }
+            var counter = Counter();
+            counter.count = 0;
+            counter.increment().increment();
+            var result = counter.count;";
+        assert_eq!(
+            helper_run_and_eval(source, "result;").unwrap(),
+            Value::Number(2.0)
+        );
+    }
+
+    #[test]
+    fn test_bound_method_remembers_instance() {
+        let source = "class Person { name() { return this.first; } }
+            var person = Person();
+            person.first = \"Jane\";
+            var method = person.name;
+            var result = method();";
+        assert_eq!(
+            helper_run_and_eval(source, "result;").unwrap(),
+            Value::String("Jane".to_string())
+        );
+    }
+
+    #[test]
+    fn test_initializer_always_returns_this() {
+        let source = "class Point { init(x, y) { this.x = x; this.y = y; return; } }
+            var point = Point(1, 2);
+            var again = point.init(3, 4);
+            var result = again == point and point.x == 3;";
+        assert_eq!(helper_run_and_eval(source, "result;").unwrap(), Value::True);
+    }
+
+    #[test]
+    fn test_property_on_non_instance_reports_runtime_error_at_name() {
+        let error = helper_run_and_eval("", "\"str\".length;").unwrap_err();
+        assert_eq!(error.kind, RuntimeErrorKind::NotAnInstance);
+        assert_eq!(error.message, "Only instances have properties.");
+        assert_eq!((error.span.column, error.span.length), (7, 6));
+    }
+
+    #[test]
+    fn test_undefined_property_reports_runtime_error() {
+        assert_eq!(
+            helper_run_and_eval("class A {} var a = A();", "a.missing;")
+                .unwrap_err()
+                .to_string(),
+            "Undefined property 'missing'.".to_string()
+        );
+    }
+
+    #[test]
+    fn test_subclass_inherits_superclass_methods() {
+        let source = "class Doughnut { cook() { return \"Fry until golden brown.\"; } }
+            class BostonCream < Doughnut {}
+            var result = BostonCream().cook();";
+        assert_eq!(
+            helper_run_and_eval(source, "result;").unwrap(),
+            Value::String("Fry until golden brown.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_super_resolves_against_defining_class() {
+        let source = "class A { method() { return \"A\"; } }
+            class B < A {
+              method() { return \"B\"; }
+              test() { return super.method(); }
+            }
+            class C < B {}
+            var result = C().test();";
+        assert_eq!(
+            helper_run_and_eval(source, "result;").unwrap(),
+            Value::String("A".to_string())
+        );
+    }
+
+    #[test]
+    fn test_inheriting_from_non_class_reports_runtime_error() {
+        let statements =
+            helper_create_stmts_from_string("var NotAClass = 1; class A < NotAClass {}");
+        let interpreter = Interpreter::new();
+        helper_resolve_stmts(&statements);
+        interpreter.execute(&statements[0]).unwrap();
+        let error = interpreter.execute(&statements[1]).unwrap_err();
+        assert_eq!(error.kind, RuntimeErrorKind::SuperclassNotClass);
+        assert_eq!(error.message, "Superclass must be a class.");
+        assert_eq!((error.span.column, error.span.length), (30, 9));
+    }
+
+    #[test]
+    fn test_operator_runtime_error_points_at_operator() {
+        let error = helper_run_and_eval("", "1 +\n  2 / 0;").unwrap_err();
+        assert_eq!(error.kind, RuntimeErrorKind::DivisionByZero);
+        assert_eq!(error.message, "Division by zero.");
+        assert_eq!(error.span.length, 1);
+        assert_eq!((error.span.line, error.span.column), (2, 5));
+    }
+
+    #[test]
+    fn test_runtime_error_records_call_stack_innermost_first() {
+        let statements = helper_create_stmts_from_string(
+            "fun inner() { return 1 / 0; }
+             fun outer() { return inner(); }
+             class A { method() { outer(); } }
+             A().method();",
+        );
+        let interpreter = Interpreter::new();
+        helper_resolve_stmts(&statements);
+        for statement in &statements[..3] {
+            interpreter.execute(statement).unwrap();
+        }
+        let error = interpreter.execute(&statements[3]).unwrap_err();
+        let trace: Vec<(&str, u32)> = error
+            .trace
+            .iter()
+            .map(|frame| (frame.function.as_str(), frame.call_site.line))
+            .collect();
+        assert_eq!(trace, vec![("inner", 2), ("outer", 3), ("method", 4)]);
+        assert!(interpreter.frames.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_unbounded_recursion_overflows_stack() {
+        let error = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(|| {
+                let statements = helper_create_stmts_from_string("fun f() { f(); } f();");
+                let interpreter = Interpreter::new();
+                helper_resolve_stmts(&statements);
+                interpreter.execute(&statements[0]).unwrap();
+                let error = interpreter.execute(&statements[1]).unwrap_err();
+                assert!(interpreter.frames.borrow().is_empty());
+                (error.kind, error.trace.len())
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(error, (RuntimeErrorKind::StackOverflow, MAX_CALL_DEPTH - 1));
+    }
+
+    #[test]
+    fn test_top_level_runtime_error_has_empty_trace() {
+        let error = helper_run_and_eval("", "1 / 0;").unwrap_err();
+        assert!(error.trace.is_empty());
     }
 }
@@ -1,10 +1,61 @@
+use std::{io::Write, path::PathBuf, time::Instant};
+
+use rustyline::{error::ReadlineError, DefaultEditor};
+
 use super::{
-    error_reporter::ErrorReporter, interpreter::Interpreter, parser::Parser, scanner::Scanner,
+    ast_json::JsonExporter,
+    ast_printer::{AstPrinter, DotPrinter},
+    error::{LoxError, ScanErrorKind},
+    error_reporter::ErrorReporter,
+    formatter::{FormatOptions, Formatter},
+    interpreter::Interpreter,
+    optimizer::Optimizer,
+    parser::Parser,
+    resolver::Resolver,
+    scanner::Scanner,
+    span::Span,
+    stmt::Stmt,
+    token_type::TokenType,
+    vm::Vm,
 };
 
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = "... ";
+// Kept in the user's home directory.
+const HISTORY_FILE: &str = ".rlox_history";
+
+const HELP: &str = "\
+:tokens <code>  show the tokens scanned from <code>
+:ast <code>     show the syntax tree parsed from <code>
+:env            list global variables and their values
+:load <file>    run a file in this session
+:reset          forget everything defined in this session
+:time <code>    run <code> and report how long it took
+:help           show this message";
+
+/// Which engine runs programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    #[default]
+    TreeWalker,
+    Vm,
+}
+
 pub struct Lox {
     error_reporter: ErrorReporter,
     interpreter: Interpreter,
+    vm: Vm,
+    backend: Backend,
+    // Whether programs are constant folded before they run.
+    optimize: bool,
+    // Shown in diagnostics for the source being run.
+    source_name: String,
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Lox {
@@ -12,45 +63,516 @@ impl Lox {
         Lox {
             error_reporter: ErrorReporter::new(),
             interpreter: Interpreter::new(),
+            vm: Vm::new(),
+            backend: Backend::default(),
+            optimize: true,
+            source_name: "<input>".to_string(),
         }
     }
 
-    pub fn main(&mut self) {
-        let args: Vec<String> = std::env::args().collect();
-        if args.len() > 2 {
-            panic!("Usage: rlox [script]");
-        } else if args.len() == 2 {
-            self.run_file(args[1].clone());
-        } else {
-            self.run_prompt();
-        }
+    /// Sets the file name diagnostics show for the next sources run.
+    pub fn set_source_name(&mut self, name: &str) {
+        self.source_name = name.to_string();
     }
 
-    fn run_file(&mut self, script: String) {
-        let source = std::fs::read_to_string(script).unwrap();
-        self.run(source)
+    /// Chooses the engine that runs programs from now on. Each keeps its own
+    /// globals.
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
     }
 
-    fn run_prompt(&mut self) {
+    /// Turns constant folding on or off for the next programs run.
+    pub fn set_optimize(&mut self, optimize: bool) {
+        self.optimize = optimize;
+    }
+
+    pub fn run_prompt(&mut self) {
+        self.set_source_name("<repl>");
+
+        let mut editor = match DefaultEditor::new() {
+            Ok(editor) => editor,
+            Err(error) => {
+                eprintln!("Could not start the REPL: {error}");
+                return;
+            }
+        };
+        let history = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+        if let Some(history) = &history {
+            // A missing history file just means this is the first session.
+            let _ = editor.load_history(history);
+        }
+
+        let mut entry = String::new();
         loop {
-            let mut input = String::new();
-            std::io::stdin().read_line(&mut input).unwrap();
-            self.run(input);
-            self.error_reporter.had_error = false;
+            let prompt = if entry.is_empty() {
+                PROMPT
+            } else {
+                CONTINUATION_PROMPT
+            };
+            match editor.readline(prompt) {
+                Ok(line) if entry.is_empty() && line.trim_start().starts_with(':') => {
+                    let _ = editor.add_history_entry(line.trim());
+                    let _ = self.run_command(line.trim(), &mut std::io::stdout());
+                    self.error_reporter.reset();
+                }
+                Ok(line) => {
+                    entry.push_str(&line);
+                    entry.push('\n');
+                    if is_incomplete(&entry) {
+                        continue;
+                    }
+
+                    let mut source = std::mem::take(&mut entry);
+                    if source.trim().is_empty() {
+                        continue;
+                    }
+                    let _ = editor.add_history_entry(source.trim_end());
+
+                    add_missing_semicolon(&mut source);
+                    let _ = self.run_source(source, true);
+                    self.error_reporter.reset();
+                }
+                // Ctrl-C abandons the entry being typed.
+                Err(ReadlineError::Interrupted) => entry.clear(),
+                Err(ReadlineError::Eof) => break,
+                Err(error) => {
+                    eprintln!("{error}");
+                    break;
+                }
+            }
+        }
+
+        if let Some(history) = &history {
+            let _ = editor.save_history(history);
+        }
+    }
+
+    /// Runs a REPL meta-command such as `:ast print 1;`, writing its output
+    /// to `out`.
+    fn run_command(&mut self, command: &str, out: &mut impl Write) -> std::io::Result<()> {
+        let (name, argument) = match command.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (command, ""),
+        };
+        let mut source = argument.to_string();
+        if name != ":tokens" {
+            add_missing_semicolon(&mut source);
+        }
+
+        match name {
+            ":tokens" => {
+                if let Ok(tokens) = self.tokens(source) {
+                    write!(out, "{tokens}")?;
+                }
+            }
+            ":ast" => {
+                if let Ok(ast) = self.ast(source) {
+                    writeln!(out, "{ast}")?;
+                }
+            }
+            ":env" => {
+                let mut bindings: Vec<_> = match self.backend {
+                    Backend::TreeWalker => self
+                        .interpreter
+                        .globals
+                        .borrow()
+                        .bindings()
+                        .map(|(name, value)| (name.to_string(), value.to_string()))
+                        .collect(),
+                    Backend::Vm => self
+                        .vm
+                        .globals()
+                        .map(|(name, value)| (name.to_string(), value.to_string()))
+                        .collect(),
+                };
+                bindings.sort();
+                for (name, value) in bindings {
+                    writeln!(out, "{name} = {value}")?;
+                }
+            }
+            ":load" => match std::fs::read_to_string(argument) {
+                Ok(source) => {
+                    let name = std::mem::replace(&mut self.source_name, argument.to_string());
+                    let _ = self.run(source);
+                    self.source_name = name;
+                }
+                Err(error) => writeln!(out, "Could not read '{argument}': {error}")?,
+            },
+            ":reset" => {
+                self.interpreter = Interpreter::new();
+                self.vm = Vm::new();
+                writeln!(out, "Session reset.")?;
+            }
+            ":time" => {
+                let start = Instant::now();
+                let _ = self.run_source(source, true);
+                writeln!(out, "Took {:.3?}.", start.elapsed())?;
+            }
+            ":help" => writeln!(out, "{HELP}")?,
+            _ => writeln!(
+                out,
+                "Unknown command '{name}'. Type :help for a list of commands."
+            )?,
+        }
+        Ok(())
+    }
+
+    /// Scans, parses, resolves and runs `source`. Every error is also
+    /// reported through the error reporter as it is found.
+    pub fn run(&mut self, source: String) -> Result<(), LoxError> {
+        self.run_source(source, false)
+    }
+
+    /// Runs a whole script and returns the process exit status.
+    pub fn run_script(&mut self, source: String) -> i32 {
+        match self.run(source) {
+            Ok(()) => 0,
+            Err(error) => error.exit_code(),
+        }
+    }
+
+    /// Reports every compile error in `source` without running it.
+    pub fn check(&mut self, source: String) -> Result<(), LoxError> {
+        self.compile(source).map(|_| ())
+    }
+
+    /// Lists the tokens in `source`, one per line with its position.
+    pub fn tokens(&mut self, source: String) -> Result<String, LoxError> {
+        self.error_reporter.set_source(&self.source_name, &source);
+        let mut scanner = Scanner::new(source, &mut self.error_reporter);
+        scanner.scan_tokens();
+        if !scanner.errors.is_empty() {
+            return Err(LoxError::Compile {
+                scan_errors: std::mem::take(&mut scanner.errors),
+                parse_errors: Vec::new(),
+                resolve_errors: Vec::new(),
+            });
+        }
+
+        let mut result = String::new();
+        for token in &scanner.tokens {
+            result.push_str(&format!(
+                "{}:{} {}\n",
+                token.span.line, token.span.column, token
+            ));
+        }
+        Ok(result)
+    }
+
+    /// Prints the syntax tree of `source` as S-expressions.
+    pub fn ast(&mut self, source: String) -> Result<String, LoxError> {
+        let statements = self.parse(source)?;
+        Ok(AstPrinter::new().print_program(&statements))
+    }
+
+    /// Serializes the syntax tree of `source` as indented JSON.
+    pub fn ast_json(&mut self, source: String) -> Result<String, LoxError> {
+        let statements = self.parse(source)?;
+        Ok(format!(
+            "{:#}",
+            JsonExporter::new().export_program(&statements)
+        ))
+    }
+
+    /// Renders the syntax tree of `source` as a Graphviz digraph.
+    pub fn ast_dot(&mut self, source: String) -> Result<String, LoxError> {
+        let statements = self.parse(source)?;
+        Ok(DotPrinter::new().print_program(&statements))
+    }
+
+    /// Prints `source` back in canonical layout, keeping its comments.
+    pub fn format(&mut self, source: String, options: FormatOptions) -> Result<String, LoxError> {
+        let (statements, comments) = self.parse_with_comments(source.clone())?;
+        Ok(Formatter::new(&source, comments, options).format(&statements))
+    }
+
+    fn run_source(&mut self, source: String, echo: bool) -> Result<(), LoxError> {
+        let statements = self.compile(source)?;
+        let statements = self.optimize(statements);
+        let error_reporter = &mut self.error_reporter;
+        let result = match (self.backend, echo) {
+            (Backend::TreeWalker, false) => self.interpreter.interpret(&statements, error_reporter),
+            (Backend::TreeWalker, true) => self
+                .interpreter
+                .interpret_echoing(&statements, error_reporter),
+            (Backend::Vm, false) => self.vm.interpret(&statements, error_reporter),
+            (Backend::Vm, true) => self.vm.interpret_echoing(&statements, error_reporter),
+        };
+        result.map_err(LoxError::Runtime)
+    }
+
+    /// Scans, parses and resolves `source`.
+    fn compile(&mut self, source: String) -> Result<Vec<Stmt>, LoxError> {
+        let statements = self.parse(source)?;
+        let resolver = Resolver::new(&mut self.error_reporter);
+        match resolver.resolve(&statements) {
+            Ok(()) => Ok(statements),
+            Err(resolve_errors) => Err(LoxError::Compile {
+                scan_errors: Vec::new(),
+                parse_errors: Vec::new(),
+                resolve_errors,
+            }),
+        }
+    }
+
+    /// Constant folds a resolved program, unless optimization is off. Folding
+    /// keeps the scope depths the resolver stored in the surviving nodes.
+    fn optimize(&self, statements: Vec<Stmt>) -> Vec<Stmt> {
+        if !self.optimize {
+            return statements;
         }
+        Optimizer::new().optimize(statements)
+    }
+
+    fn parse(&mut self, source: String) -> Result<Vec<Stmt>, LoxError> {
+        self.parse_with_comments(source)
+            .map(|(statements, _)| statements)
     }
 
-    fn run(&mut self, source: String) {
+    /// Scans and parses `source`, also returning the spans of its comments.
+    fn parse_with_comments(&mut self, source: String) -> Result<(Vec<Stmt>, Vec<Span>), LoxError> {
+        self.error_reporter.set_source(&self.source_name, &source);
+        // The scanner finishes before the parser starts, so hold diagnostics
+        // back to print them in source order.
+        self.error_reporter.hold();
         let mut scanner = Scanner::new(source, &mut self.error_reporter);
         scanner.scan_tokens();
+        let scan_errors = std::mem::take(&mut scanner.errors);
+        let comments = scanner.comments();
         let tokens = scanner.tokens;
         let mut parser = Parser::new(tokens, &mut self.error_reporter);
+        let parsed = parser.parse();
+        self.error_reporter.release();
+
+        match parsed {
+            Ok(statements) if scan_errors.is_empty() => Ok((statements, comments)),
+            Ok(_) => Err(LoxError::Compile {
+                scan_errors,
+                parse_errors: Vec::new(),
+                resolve_errors: Vec::new(),
+            }),
+            Err(parse_errors) => Err(LoxError::Compile {
+                scan_errors,
+                parse_errors,
+                resolve_errors: Vec::new(),
+            }),
+        }
+    }
+}
+
+/// Whether a REPL entry needs more lines: it has unclosed brackets, or ends
+/// inside a string or block comment.
+fn is_incomplete(source: &str) -> bool {
+    let mut error_reporter = ErrorReporter::quiet();
+    let mut scanner = Scanner::new(source.to_string(), &mut error_reporter);
+    scanner.scan_tokens();
+
+    let unterminated = scanner.errors.iter().any(|error| {
+        matches!(
+            error.kind,
+            ScanErrorKind::UnterminatedString | ScanErrorKind::UnterminatedComment
+        )
+    });
+    let mut depth = 0;
+    for token in &scanner.tokens {
+        match token.token_type {
+            TokenType::LeftParen | TokenType::LeftBrace => depth += 1,
+            TokenType::RightParen | TokenType::RightBrace => depth -= 1,
+            _ => {}
+        }
+    }
+    unterminated || depth > 0
+}
+
+/// Lets `1 + 2` stand for `1 + 2;` in the REPL.
+fn add_missing_semicolon(source: &mut String) {
+    if is_missing_semicolon(source) {
+        source.insert(source.trim_end().len(), ';');
+    }
+}
+
+/// Whether a REPL entry ends without the `;` or `}` that closes a statement.
+fn is_missing_semicolon(source: &str) -> bool {
+    let mut error_reporter = ErrorReporter::quiet();
+    let mut scanner = Scanner::new(source.to_string(), &mut error_reporter);
+    scanner.scan_tokens();
+
+    let last = scanner.tokens.iter().rev().nth(1);
+    !matches!(
+        last.map(|token| token.token_type),
+        None | Some(TokenType::Semicolon) | Some(TokenType::RightBrace)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        error::{ParseErrorKind, ResolveErrorKind, RuntimeErrorKind, ScanErrorKind},
+        error_reporter::{SCAN_ERROR, SYNTAX_ERROR},
+    };
+
+    #[test]
+    fn test_run_returns_compile_errors() {
+        let mut lox = Lox::new();
+        match lox.run("var x = @;".to_string()) {
+            Err(LoxError::Compile {
+                scan_errors,
+                parse_errors,
+                ..
+            }) => {
+                assert_eq!(scan_errors[0].kind, ScanErrorKind::UnexpectedCharacter);
+                assert_eq!(parse_errors[0].kind, ParseErrorKind::ExpectedExpression);
+            }
+            result => panic!("Expected compile error, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn test_unterminated_string_reports_no_follow_on_errors() {
+        let mut lox = Lox::new();
+        let source = "print \"a\\\"b\";\nvar s = \"line\nbreak\";";
+        assert!(lox.run(source.to_string()).is_err());
+        let reported: Vec<(&str, u32)> = lox
+            .error_reporter
+            .diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.code, diagnostic.line))
+            .collect();
+        assert_eq!(reported, vec![(SYNTAX_ERROR, 1), (SCAN_ERROR, 3)]);
+    }
+
+    #[test]
+    fn test_run_returns_resolution_errors_as_compile_errors() {
+        let mut lox = Lox::new();
+        match lox.run("return 1;".to_string()) {
+            Err(LoxError::Compile { resolve_errors, .. }) => {
+                assert_eq!(resolve_errors[0].kind, ResolveErrorKind::ReturnAtTopLevel)
+            }
+            result => panic!("Expected compile error, got {result:?}"),
+        }
+    }
 
-        if let Ok(expr) = parser.parse() {
-            // let mut ast_printer = AstPrinter::new();
-            // let ast = expr.accept(&mut ast_printer);
-            // println!("{}", ast)
-            self.interpreter.interpret(&expr, &mut self.error_reporter)
+    #[test]
+    fn test_run_returns_runtime_error() {
+        let mut lox = Lox::new();
+        match lox.run("var a = 1;\na / 0;".to_string()) {
+            Err(LoxError::Runtime(error)) => {
+                assert_eq!(error.kind, RuntimeErrorKind::DivisionByZero);
+                assert_eq!(error.span.line, 2);
+            }
+            result => panic!("Expected runtime error, got {result:?}"),
         }
+        assert!(lox.run("print a;".to_string()).is_ok());
+    }
+
+    #[test]
+    fn test_folded_program_keeps_runtime_errors_and_bindings() {
+        let mut lox = Lox::new();
+        let error = lox.run("print 1 / (2 - 2);".to_string()).unwrap_err();
+        assert!(
+            matches!(error, LoxError::Runtime(error) if error.kind == RuntimeErrorKind::DivisionByZero)
+        );
+        let source = "var a = 1; { var a = (2); fun f() { return a + (1 + 1); } a = f(); }";
+        assert!(lox.run(source.to_string()).is_ok());
+    }
+
+    #[test]
+    fn test_repl_waits_for_unbalanced_brackets() {
+        assert!(is_incomplete("fun f() {\n"));
+        assert!(is_incomplete("print (1 +\n"));
+        assert!(is_incomplete("print \"multi\nline"));
+        assert!(!is_incomplete("fun f() {}\n"));
+        assert!(!is_incomplete("print 1;\n"));
+        // Too many closing brackets is a syntax error, not more input.
+        assert!(!is_incomplete(")\n"));
+    }
+
+    #[test]
+    fn test_repl_adds_missing_semicolon() {
+        assert!(is_missing_semicolon("1 + 2\n"));
+        assert!(!is_missing_semicolon("1 + 2;\n"));
+        assert!(!is_missing_semicolon("fun f() {}\n"));
+    }
+
+    fn helper_command(lox: &mut Lox, command: &str) -> String {
+        let mut out = Vec::new();
+        lox.run_command(command, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_tokens_command_lists_tokens_with_positions() {
+        let mut lox = Lox::new();
+        assert_eq!(
+            helper_command(&mut lox, ":tokens var a;"),
+            "1:1 Var var\n1:5 Identifier a\n1:6 Semicolon ;\n1:7 EOF \n"
+        );
+    }
+
+    #[test]
+    fn test_ast_command_prints_tree() {
+        let mut lox = Lox::new();
+        assert_eq!(
+            helper_command(&mut lox, ":ast 1 + 2 * 3"),
+            "(; (+ 1 (* 2 3)))\n"
+        );
+    }
+
+    #[test]
+    fn test_env_and_reset_commands() {
+        let mut lox = Lox::new();
+        lox.run("var b = \"two\"; var a = 1;".to_string()).unwrap();
+        assert_eq!(helper_command(&mut lox, ":env"), "a = 1\nb = two\n");
+        helper_command(&mut lox, ":reset");
+        assert_eq!(helper_command(&mut lox, ":env"), "");
+    }
+
+    #[test]
+    fn test_env_command_lists_vm_globals() {
+        let mut lox = Lox::new();
+        lox.set_backend(Backend::Vm);
+        lox.run("var b = \"two\"; fun a() {}".to_string()).unwrap();
+        assert_eq!(helper_command(&mut lox, ":env"), "a = <fn a>\nb = two\n");
+    }
+
+    #[test]
+    fn test_load_command_runs_file_in_session() {
+        let path = std::env::temp_dir().join(format!(
+            "rlox_test_load_command_runs_file_in_session_{}.lox",
+            std::process::id()
+        ));
+        std::fs::write(&path, "var loaded = 42;").unwrap();
+        let mut lox = Lox::new();
+        helper_command(&mut lox, &format!(":load {}", path.display()));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(helper_command(&mut lox, ":env"), "loaded = 42\n");
+    }
+
+    #[test]
+    fn test_unknown_command_points_to_help() {
+        let mut lox = Lox::new();
+        assert!(helper_command(&mut lox, ":nope").contains(":help"));
+        assert!(helper_command(&mut lox, ":help").contains(":tokens"));
+    }
+
+    #[test]
+    fn test_exit_codes_match_jlox() {
+        let mut lox = Lox::new();
+        assert_eq!(lox.run("print ;".to_string()).unwrap_err().exit_code(), 65);
+        assert_eq!(
+            lox.run("print -nil;".to_string()).unwrap_err().exit_code(),
+            70
+        );
+    }
+
+    #[test]
+    fn test_program_with_syntax_error_is_not_run() {
+        let mut lox = Lox::new();
+        assert!(lox.run("var a = 1;\nprint (;".to_string()).is_err());
+        let error = lox.run("a;".to_string()).unwrap_err();
+        assert!(
+            matches!(error, LoxError::Runtime(error) if error.kind == RuntimeErrorKind::UndefinedVariable)
+        );
     }
 }
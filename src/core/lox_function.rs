@@ -0,0 +1,72 @@
+use std::{cell::RefCell, fmt::Display, rc::Rc};
+
+use super::{
+    environment::Environment,
+    error::Result,
+    expr::{LoxCallable, Value},
+    interpreter::Interpreter,
+    stmt::FunctionDecl,
+};
+
+pub struct LoxFunction {
+    declaration: Rc<FunctionDecl>,
+    closure: Rc<RefCell<Environment>>,
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        declaration: Rc<FunctionDecl>,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> LoxFunction {
+        LoxFunction {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    /// Returns a copy of this method whose closure binds `this` to `instance`.
+    pub fn bind(&self, instance: Value) -> LoxFunction {
+        let mut environment = Environment::new_enclosing(self.closure.clone());
+        environment.define("this".to_string(), instance);
+        LoxFunction::new(
+            self.declaration.clone(),
+            Rc::new(RefCell::new(environment)),
+            self.is_initializer,
+        )
+    }
+}
+
+impl LoxCallable for LoxFunction {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn name(&self) -> String {
+        self.declaration.name.lexeme.clone()
+    }
+
+    fn call(self: Rc<Self>, interpreter: &Interpreter, arguments: Vec<Value>) -> Result<Value> {
+        let mut environment = Environment::new_enclosing(self.closure.clone());
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(param.lexeme.clone(), argument);
+        }
+
+        let value = interpreter.execute_block(&self.declaration.body, environment)?;
+
+        // An initializer always returns `this`, even from an early `return;`.
+        if self.is_initializer {
+            return Ok(self.closure.borrow().get_here("this").unwrap_or(Value::Nil));
+        }
+
+        Ok(value.unwrap_or(Value::Nil))
+    }
+}
+
+impl Display for LoxFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.lexeme)
+    }
+}
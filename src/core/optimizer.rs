@@ -0,0 +1,305 @@
+use std::rc::Rc;
+
+use super::{
+    expr::{Expr, Value},
+    interpreter::Interpreter,
+    stmt::{FunctionDecl, Stmt},
+};
+
+/// Simplifies a parsed program before it runs: operators applied to
+/// literals are folded into a single literal, ternaries with a constant
+/// condition are replaced by the branch they pick, and groupings, which only
+/// guide parsing, are dropped.
+pub struct Optimizer {
+    // Folds constants with the runtime's own rules. Anything that would fail,
+    // like a division by zero, is left in place to fail when it runs.
+    interpreter: Interpreter,
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Optimizer {
+    pub fn new() -> Self {
+        Optimizer {
+            interpreter: Interpreter::new(),
+        }
+    }
+
+    pub fn optimize(&self, statements: Vec<Stmt>) -> Vec<Stmt> {
+        statements
+            .into_iter()
+            .map(|statement| self.optimize_stmt(statement))
+            .collect()
+    }
+
+    fn optimize_stmt(&self, statement: Stmt) -> Stmt {
+        match statement {
+            Stmt::Expression { expression, span } => Stmt::Expression {
+                expression: self.optimize_expr(expression),
+                span,
+            },
+            Stmt::Print { expression, span } => Stmt::Print {
+                expression: self.optimize_expr(expression),
+                span,
+            },
+            Stmt::Var {
+                name,
+                initializer,
+                span,
+            } => Stmt::Var {
+                name,
+                initializer: initializer.map(|initializer| self.optimize_expr(initializer)),
+                span,
+            },
+            Stmt::Block { statements, span } => Stmt::Block {
+                statements: self.optimize(statements),
+                span,
+            },
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                span,
+            } => Stmt::If {
+                condition: self.optimize_expr(condition),
+                then_branch: Box::new(self.optimize_stmt(*then_branch)),
+                else_branch: else_branch.map(|branch| Box::new(self.optimize_stmt(*branch))),
+                span,
+            },
+            Stmt::While {
+                condition,
+                body,
+                for_clauses,
+                span,
+            } => Stmt::While {
+                condition: self.optimize_expr(condition),
+                body: Box::new(self.optimize_stmt(*body)),
+                for_clauses,
+                span,
+            },
+            Stmt::Function(declaration) => Stmt::Function(self.optimize_function(declaration)),
+            Stmt::Return {
+                keyword,
+                value,
+                span,
+            } => Stmt::Return {
+                keyword,
+                value: value.map(|value| self.optimize_expr(value)),
+                span,
+            },
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                body_span,
+                span,
+            } => Stmt::Class {
+                name,
+                superclass,
+                methods: methods
+                    .into_iter()
+                    .map(|method| self.optimize_function(method))
+                    .collect(),
+                body_span,
+                span,
+            },
+        }
+    }
+
+    /// Declarations already shared with a running program are left alone.
+    fn optimize_function(&self, declaration: Rc<FunctionDecl>) -> Rc<FunctionDecl> {
+        match Rc::try_unwrap(declaration) {
+            Ok(declaration) => Rc::new(FunctionDecl {
+                body: self.optimize(declaration.body),
+                ..declaration
+            }),
+            Err(declaration) => declaration,
+        }
+    }
+
+    pub fn optimize_expr(&self, expression: Expr) -> Expr {
+        match expression {
+            Expr::Grouping { expression, .. } => self.optimize_expr(*expression),
+            Expr::Unary {
+                operator,
+                right,
+                span,
+            } => self.fold(Expr::Unary {
+                operator,
+                right: Box::new(self.optimize_expr(*right)),
+                span,
+            }),
+            Expr::Binary {
+                left,
+                operator,
+                right,
+                span,
+            } => self.fold(Expr::Binary {
+                left: Box::new(self.optimize_expr(*left)),
+                operator,
+                right: Box::new(self.optimize_expr(*right)),
+                span,
+            }),
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+                span,
+            } => match self.optimize_expr(*condition) {
+                Expr::Literal { value, .. } => {
+                    if matches!(value, None | Some(Value::Nil) | Some(Value::False)) {
+                        self.optimize_expr(*else_branch)
+                    } else {
+                        self.optimize_expr(*then_branch)
+                    }
+                }
+                condition => Expr::Ternary {
+                    condition: Box::new(condition),
+                    then_branch: Box::new(self.optimize_expr(*then_branch)),
+                    else_branch: Box::new(self.optimize_expr(*else_branch)),
+                    span,
+                },
+            },
+            Expr::Assign {
+                name,
+                value,
+                depth,
+                span,
+            } => Expr::Assign {
+                name,
+                value: Box::new(self.optimize_expr(*value)),
+                depth,
+                span,
+            },
+            Expr::Logical {
+                left,
+                operator,
+                right,
+                span,
+            } => Expr::Logical {
+                left: Box::new(self.optimize_expr(*left)),
+                operator,
+                right: Box::new(self.optimize_expr(*right)),
+                span,
+            },
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+                span,
+            } => Expr::Call {
+                callee: Box::new(self.optimize_expr(*callee)),
+                paren,
+                arguments: arguments
+                    .into_iter()
+                    .map(|argument| self.optimize_expr(argument))
+                    .collect(),
+                span,
+            },
+            Expr::Get { object, name, span } => Expr::Get {
+                object: Box::new(self.optimize_expr(*object)),
+                name,
+                span,
+            },
+            Expr::Set {
+                object,
+                name,
+                value,
+                span,
+            } => Expr::Set {
+                object: Box::new(self.optimize_expr(*object)),
+                name,
+                value: Box::new(self.optimize_expr(*value)),
+                span,
+            },
+            Expr::Literal { .. }
+            | Expr::Variable { .. }
+            | Expr::This { .. }
+            | Expr::Super { .. } => expression,
+        }
+    }
+
+    /// Replaces an operator whose operands are all literals with its value.
+    fn fold(&self, expression: Expr) -> Expr {
+        let constant = match &expression {
+            Expr::Unary { right, .. } => is_literal(right),
+            Expr::Binary { left, right, .. } => is_literal(left) && is_literal(right),
+            _ => false,
+        };
+        if !constant {
+            return expression;
+        }
+
+        match self.interpreter.evalute(&expression) {
+            Ok(value) => Expr::Literal {
+                value: Some(value),
+                span: expression.span(),
+            },
+            Err(_) => expression,
+        }
+    }
+}
+
+fn is_literal(expression: &Expr) -> bool {
+    matches!(expression, Expr::Literal { .. })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        ast_printer::AstPrinter, test_utils::tests::helper_create_stmts_from_string,
+    };
+
+    fn helper_optimize(source: &str) -> String {
+        let statements = helper_create_stmts_from_string(source);
+        let statements = Optimizer::new().optimize(statements);
+        AstPrinter::new().print_program(&statements)
+    }
+
+    #[test]
+    fn test_folds_arithmetic_over_literals() {
+        assert_eq!(helper_optimize("print 1 + 2 * (3 - 1);"), "(print 5)");
+        assert_eq!(helper_optimize("print -(4 / 8);"), "(print -0.5)");
+        assert_eq!(
+            helper_optimize("print \"a\" + 1 + nil;"),
+            "(print \"a1nil\")"
+        );
+        assert_eq!(helper_optimize("print !(1 < 2) == false;"), "(print true)");
+    }
+
+    #[test]
+    fn test_folds_only_constant_operands() {
+        assert_eq!(helper_optimize("print x + (1 + 2);"), "(print (+ x 3))");
+        assert_eq!(helper_optimize("print (x) * 2;"), "(print (* x 2))");
+    }
+
+    #[test]
+    fn test_keeps_operations_that_fail_at_runtime() {
+        assert_eq!(helper_optimize("print 1 / (1 - 1);"), "(print (/ 1 0))");
+        assert_eq!(helper_optimize("print -\"a\";"), "(print (- \"a\"))");
+        assert_eq!(helper_optimize("print 1 < nil;"), "(print (< 1 nil))");
+    }
+
+    #[test]
+    fn test_collapses_ternaries_with_constant_conditions() {
+        assert_eq!(helper_optimize("print 1 > 2 ? a : b;"), "(print b)");
+        assert_eq!(helper_optimize("print \"\" ? a : b;"), "(print a)");
+        assert_eq!(
+            helper_optimize("print c ? 1 + 1 : 2;"),
+            "(print (if c then 2 else 2))"
+        );
+    }
+
+    #[test]
+    fn test_optimizes_inside_functions_and_classes() {
+        assert_eq!(
+            helper_optimize("fun f() { return 2 * 3; } class A { m() { print (1); } }"),
+            "(fun f () (return 6))\n(class A (method m () (print 1)))"
+        );
+    }
+}
@@ -1,98 +1,490 @@
-use anyhow::{anyhow, Result};
+use std::rc::Rc;
 
 use super::{
+    error::{ParseError, ParseErrorKind},
     error_reporter::ErrorReporter,
-    expr::{Expr, Value},
-    stmt::Stmt,
+    expr::{Depth, Expr, Value},
+    span::Span,
+    stmt::{ForClauses, FunctionDecl, Stmt},
     token::Token,
     token_type::TokenType,
 };
 
+const MAX_ARGUMENTS: usize = 255;
+
+type Result<T, E = ParseError> = std::result::Result<T, E>;
+
 pub struct Parser<'a> {
     tokens: Vec<Token>,
     current: usize,
+    errors: Vec<ParseError>,
+    // How many blocks enclose the current token.
+    block_depth: usize,
 
     error_reporter: &'a mut ErrorReporter,
 }
 
 impl Parser<'_> {
-    pub fn new(tokens: Vec<Token>, error_reporter: &mut ErrorReporter) -> Parser {
+    pub fn new(tokens: Vec<Token>, error_reporter: &mut ErrorReporter) -> Parser<'_> {
         Parser {
             tokens,
             current: 0,
-            error_reporter: error_reporter,
+            errors: Vec::new(),
+            block_depth: 0,
+            error_reporter,
         }
     }
 
-    pub fn parse(&mut self) -> Result<Vec<Stmt>> {
+    /// Parses the whole program. On a syntax error the parser skips ahead to
+    /// the next statement boundary and keeps going, so every error in the
+    /// source is reported and returned rather than only the first.
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
         let mut statements = Vec::new();
         while !self.is_at_end() {
-            statements.push(self.statement()?);
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
         }
 
-        Ok(statements)
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
     }
 
-    fn declaration(&mut self) -> Result<Stmt> {
+    fn declaration(&mut self) -> Option<Stmt> {
+        match self.try_declaration() {
+            Ok(statement) => Some(statement),
+            Err(_) => {
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn try_declaration(&mut self) -> Result<Stmt> {
+        if self.r#match(vec![TokenType::Class]) {
+            return self.class_declaration();
+        }
+
+        if self.r#match(vec![TokenType::Fun]) {
+            return self.function("function");
+        }
+
         if self.r#match(vec![TokenType::Var]) {
             return self.var_declaration();
         }
 
-        return self.statement();
+        self.statement()
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt> {
+        let start = self.previous().span;
+        let name = self.consume(TokenType::Identifier, "Expect class name.")?;
+
+        let superclass = if self.r#match(vec![TokenType::Less]) {
+            self.consume(TokenType::Identifier, "Expect superclass name.")?;
+            Some(Expr::Variable {
+                name: self.previous().clone(),
+                depth: Depth::default(),
+            })
+        } else {
+            None
+        };
+
+        let body_start = self
+            .consume(TokenType::LeftBrace, "Expect '{' before class body.")?
+            .span;
+
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let start = self.peek().span;
+            methods.push(self.function_declaration("method", start)?);
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+        Ok(Stmt::Class {
+            name,
+            superclass,
+            methods,
+            body_span: self.span_from(body_start),
+            span: self.span_from(start),
+        })
     }
 
-    // fn var_declaration(&mut self) -> Result<Stmt> {
-    //     let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
+    fn function(&mut self, kind: &str) -> Result<Stmt> {
+        let start = self.previous().span;
+        Ok(Stmt::Function(self.function_declaration(kind, start)?))
+    }
+
+    fn function_declaration(&mut self, kind: &str, start: Span) -> Result<Rc<FunctionDecl>> {
+        let name = self.consume(TokenType::Identifier, &format!("Expect {kind} name."))?;
+        self.consume(
+            TokenType::LeftParen,
+            &format!("Expect '(' after {kind} name."),
+        )?;
+
+        let mut params = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    self.error(
+                        ParseErrorKind::TooManyParameters,
+                        self.peek().clone(),
+                        &format!("Can't have more than {MAX_ARGUMENTS} parameters."),
+                    );
+                }
+
+                params.push(self.consume(TokenType::Identifier, "Expect parameter name.")?);
+
+                if !self.r#match(vec![TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+
+        let body_start = self
+            .consume(
+                TokenType::LeftBrace,
+                &format!("Expect '{{' before {kind} body."),
+            )?
+            .span;
+        let body = self.block()?;
+
+        Ok(Rc::new(FunctionDecl {
+            name,
+            params,
+            body,
+            body_span: self.span_from(body_start),
+            span: self.span_from(start),
+        }))
+    }
 
-    //     let mut initializer = None;
-    //     if self.r#match(vec![TokenType::Equal]) {
-    //         initializer = Some(self.expression()?);
-    //     }
+    fn var_declaration(&mut self) -> Result<Stmt> {
+        let start = self.previous().span;
+        let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
+
+        let mut initializer = None;
+        if self.r#match(vec![TokenType::Equal]) {
+            initializer = Some(self.expression()?);
+        }
+
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        )?;
+        Ok(Stmt::Var {
+            name,
+            initializer,
+            span: self.span_from(start),
+        })
+    }
 
-    //     self.consume(TokenType::Semicolon, "Expect ';' after variable declaration.")?;
-    //     Ok(Stmt::Var {
-    //         name,
-    //         initializer,
-    //     })
-    // }
     fn statement(&mut self) -> Result<Stmt> {
+        if self.r#match(vec![TokenType::For]) {
+            return self.for_statement();
+        }
+
+        if self.r#match(vec![TokenType::If]) {
+            return self.if_statement();
+        }
+
+        if self.r#match(vec![TokenType::While]) {
+            return self.while_statement();
+        }
+
         if self.r#match(vec![TokenType::Print]) {
             return self.print_statement();
         }
 
-        return self.expression_statement();
+        if self.r#match(vec![TokenType::Return]) {
+            return self.return_statement();
+        }
+
+        if self.r#match(vec![TokenType::LeftBrace]) {
+            let start = self.previous().span;
+            let statements = self.block()?;
+            return Ok(Stmt::Block {
+                statements,
+                span: self.span_from(start),
+            });
+        }
+
+        self.expression_statement()
+    }
+
+    fn for_statement(&mut self) -> Result<Stmt> {
+        let start = self.previous().span;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+
+        let initializer = if self.r#match(vec![TokenType::Semicolon]) {
+            None
+        } else if self.r#match(vec![TokenType::Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let has_condition = !self.check(TokenType::Semicolon);
+        let condition = if has_condition {
+            self.expression()?
+        } else {
+            Expr::Literal {
+                value: Some(Value::True),
+                span: self.peek().span,
+            }
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
+
+        let increment = if !self.check(TokenType::RightParen) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
+
+        let mut body = self.statement()?;
+        let span = self.span_from(start);
+        let for_clauses = ForClauses {
+            initializer: initializer.is_some(),
+            condition: has_condition,
+            increment: increment.is_some(),
+        };
+
+        // Desugar into `{ initializer; while (condition) { body; increment; } }`
+        if let Some(increment) = increment {
+            let increment = Stmt::Expression {
+                span: increment.span(),
+                expression: increment,
+            };
+            body = Stmt::Block {
+                span: body.span().to(increment.span()),
+                statements: vec![body, increment],
+            };
+        }
+
+        body = Stmt::While {
+            condition,
+            body: Box::new(body),
+            for_clauses: Some(for_clauses),
+            span,
+        };
+
+        if let Some(initializer) = initializer {
+            body = Stmt::Block {
+                statements: vec![initializer, body],
+                span,
+            };
+        }
+
+        Ok(body)
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt> {
+        let start = self.previous().span;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
+
+        let then_branch = Box::new(self.statement()?);
+        // An `else` binds to the nearest preceding `if`.
+        let else_branch = if self.r#match(vec![TokenType::Else]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+
+        Ok(Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+            span: self.span_from(start),
+        })
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt> {
+        let start = self.previous().span;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
+        let body = self.statement()?;
+
+        Ok(Stmt::While {
+            condition,
+            body: Box::new(body),
+            for_clauses: None,
+            span: self.span_from(start),
+        })
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>> {
+        let mut statements = Vec::new();
+
+        // Errors are recovered from at the next statement inside the block,
+        // so one mistake does not abandon the whole enclosing declaration.
+        self.block_depth += 1;
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+        self.block_depth -= 1;
+
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
+        Ok(statements)
     }
 
     fn print_statement(&mut self) -> Result<Stmt> {
-        let expr = self.expression()?;
+        let start = self.previous().span;
+        let expression = self.expression()?;
         self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
-        Ok(Stmt::Print(expr))
+        Ok(Stmt::Print {
+            expression,
+            span: self.span_from(start),
+        })
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt> {
+        let keyword = self.previous().clone();
+        let value = if !self.check(TokenType::Semicolon) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+        Ok(Stmt::Return {
+            span: self.span_from(keyword.span),
+            keyword,
+            value,
+        })
     }
 
     fn expression_statement(&mut self) -> Result<Stmt> {
-        let expr = self.expression()?;
+        let start = self.peek().span;
+        let expression = self.expression()?;
         self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
-        Ok(Stmt::Expression(expr))
+        Ok(Stmt::Expression {
+            expression,
+            span: self.span_from(start),
+        })
     }
 
     fn expression(&mut self) -> Result<Expr> {
-        let mut expr = self.equality()?;
+        self.assignment()
+    }
+
+    fn assignment(&mut self) -> Result<Expr> {
+        let expr = self.comma()?;
+        self.finish_assignment(expr, Self::assignment)
+    }
+
+    // Call arguments are separated by commas, so an argument is parsed below
+    // the comma operator.
+    fn argument(&mut self) -> Result<Expr> {
+        let expr = self.ternary()?;
+        self.finish_assignment(expr, Self::argument)
+    }
+
+    fn finish_assignment(
+        &mut self,
+        expr: Expr,
+        value: fn(&mut Self) -> Result<Expr>,
+    ) -> Result<Expr> {
+        if self.r#match(vec![TokenType::Equal]) {
+            let equals = self.previous().clone();
+            let value = value(self)?;
+
+            let span = expr.span().to(value.span());
+            return match expr {
+                Expr::Variable { name, depth } => Ok(Expr::Assign {
+                    name,
+                    value: Box::new(value),
+                    depth,
+                    span,
+                }),
+                Expr::Get { object, name, .. } => Ok(Expr::Set {
+                    object,
+                    name,
+                    value: Box::new(value),
+                    span,
+                }),
+                _ => {
+                    self.error(
+                        ParseErrorKind::InvalidAssignmentTarget,
+                        equals,
+                        "Invalid assignment target.",
+                    );
+                    Ok(expr)
+                }
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn comma(&mut self) -> Result<Expr> {
+        let mut expr = self.ternary()?;
+
+        while self.r#match(vec![TokenType::Comma]) {
+            let operator = self.previous().clone();
+            let right = self.ternary()?;
+            expr = Expr::Binary {
+                span: expr.span().to(right.span()),
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn ternary(&mut self) -> Result<Expr> {
+        let mut expr = self.or()?;
 
         while self.r#match(vec![TokenType::Question]) {
             let then_branch = self.expression()?;
             self.consume(TokenType::Colon, "Expect ':' after then branch.")?;
-            let else_branch = self.expression()?;
+            let else_branch = self.ternary()?;
             expr = Expr::Ternary {
+                span: expr.span().to(else_branch.span()),
                 condition: Box::new(expr),
                 then_branch: Box::new(then_branch),
                 else_branch: Box::new(else_branch),
             };
         }
 
-        while self.r#match(vec![TokenType::Comma]) {
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+
+        while self.r#match(vec![TokenType::Or]) {
+            let operator = self.previous().clone();
+            let right = self.and()?;
+            expr = Expr::Logical {
+                span: expr.span().to(right.span()),
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.equality()?;
+
+        while self.r#match(vec![TokenType::And]) {
             let operator = self.previous().clone();
             let right = self.equality()?;
-            expr = Expr::Binary {
+            expr = Expr::Logical {
+                span: expr.span().to(right.span()),
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
@@ -109,6 +501,7 @@ impl Parser<'_> {
             let operator = self.previous().clone();
             let right = self.comparison()?;
             expr = Expr::Binary {
+                span: expr.span().to(right.span()),
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
@@ -130,6 +523,7 @@ impl Parser<'_> {
             let operator = self.previous().clone();
             let right = self.term()?;
             expr = Expr::Binary {
+                span: expr.span().to(right.span()),
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
@@ -146,6 +540,7 @@ impl Parser<'_> {
             let operator = self.previous().clone();
             let right = self.factor()?;
             expr = Expr::Binary {
+                span: expr.span().to(right.span()),
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
@@ -162,6 +557,7 @@ impl Parser<'_> {
             let operator = self.previous().clone();
             let right = self.unary()?;
             expr = Expr::Binary {
+                span: expr.span().to(right.span()),
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
@@ -176,59 +572,210 @@ impl Parser<'_> {
             let operator = self.previous().clone();
             let right = self.unary()?;
             return Ok(Expr::Unary {
+                span: operator.span.to(right.span()),
                 operator,
                 right: Box::new(right),
             });
         }
 
-        self.primary()
+        self.call()
+    }
+
+    fn call(&mut self) -> Result<Expr> {
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.r#match(vec![TokenType::LeftParen]) {
+                expr = self.finish_call(expr)?;
+            } else if self.r#match(vec![TokenType::Dot]) {
+                let name =
+                    self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
+                expr = Expr::Get {
+                    span: expr.span().to(name.span),
+                    object: Box::new(expr),
+                    name,
+                };
+            } else {
+                break;
+            }
+        }
+
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr> {
+        let mut arguments = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    self.error(
+                        ParseErrorKind::TooManyArguments,
+                        self.peek().clone(),
+                        &format!("Can't have more than {MAX_ARGUMENTS} arguments."),
+                    );
+                }
+
+                arguments.push(self.argument()?);
+
+                if !self.r#match(vec![TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
+
+        Ok(Expr::Call {
+            span: callee.span().to(paren.span),
+            callee: Box::new(callee),
+            paren,
+            arguments,
+        })
     }
 
     fn primary(&mut self) -> Result<Expr> {
         if self.r#match(vec![TokenType::False]) {
-            return Ok(Expr::Literal(Some(Value::False)));
+            return Ok(self.literal(Value::False));
         }
 
         if self.r#match(vec![TokenType::True]) {
-            return Ok(Expr::Literal(Some(Value::True)));
+            return Ok(self.literal(Value::True));
         }
 
         if self.r#match(vec![TokenType::Nil]) {
-            return Ok(Expr::Literal(Some(Value::Nil)));
+            return Ok(self.literal(Value::Nil));
         }
 
         if self.r#match(vec![TokenType::Number]) {
-            return Ok(Expr::Literal(Some(Value::to_number(
-                &self.previous().lexeme,
-            ))));
+            return Ok(self.literal(Value::to_number(&self.previous().lexeme)));
         }
 
         if self.r#match(vec![TokenType::String]) {
-            return Ok(Expr::Literal(Some(Value::to_string(
-                &self.previous().lexeme[1..self.previous().lexeme.len() - 1],
-            ))));
+            let lexeme = &self.previous().lexeme;
+            return Ok(self.literal(Value::to_string(&lexeme[1..lexeme.len() - 1])));
+        }
+
+        if self.r#match(vec![TokenType::Super]) {
+            let keyword = self.previous().clone();
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+            let method = self.consume(TokenType::Identifier, "Expect superclass method name.")?;
+            return Ok(Expr::Super {
+                span: keyword.span.to(method.span),
+                keyword,
+                method,
+                depth: Depth::default(),
+            });
+        }
+
+        if self.r#match(vec![TokenType::This]) {
+            return Ok(Expr::This {
+                keyword: self.previous().clone(),
+                depth: Depth::default(),
+            });
+        }
+
+        if self.r#match(vec![TokenType::Identifier]) {
+            return Ok(Expr::Variable {
+                name: self.previous().clone(),
+                depth: Depth::default(),
+            });
         }
 
         if self.r#match(vec![TokenType::LeftParen]) {
+            let start = self.previous().span;
             let expr = self.expression()?;
             self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
             return Ok(Expr::Grouping {
                 expression: Box::new(expr),
+                span: self.span_from(start),
             });
         }
 
-        Err(anyhow!("Expect expression."))
+        Err(self.error(
+            ParseErrorKind::ExpectedExpression,
+            self.peek().clone(),
+            "Expect expression.",
+        ))
+    }
+
+    /// A literal spanning the token just consumed.
+    fn literal(&self, value: Value) -> Expr {
+        Expr::Literal {
+            value: Some(value),
+            span: self.previous().span,
+        }
+    }
+
+    /// The span from `start` to the end of the token just consumed.
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous().span)
     }
 
-    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<()> {
+    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<Token> {
         if self.check(token_type) {
-            self.advance();
-            Ok(())
+            Ok(self.advance().clone())
         } else {
-            let message = message.to_string();
-            self.error_reporter
-                .token_error(self.peek().clone(), &message);
-            Err(anyhow!(message))
+            Err(self.error(ParseErrorKind::ExpectedToken, self.peek().clone(), message))
+        }
+    }
+
+    /// Reports a syntax error and records it. Returning the error to the
+    /// caller is optional: errors that leave the parser in a known state,
+    /// like an invalid assignment target, are reported without unwinding.
+    fn error(&mut self, kind: ParseErrorKind, token: Token, message: &str) -> ParseError {
+        let error = ParseError::new(kind, &token, message);
+        if !self.follows_scan_error(&token) {
+            self.error_reporter.token_error(token.clone(), message);
+            self.errors.push(error.clone());
+        }
+        error
+    }
+
+    /// Whether an error at `token` is only a consequence of an error token,
+    /// which the scanner has already reported. Error tokens run to the end
+    /// of the input, so this covers the end of input right after one.
+    fn follows_scan_error(&self, token: &Token) -> bool {
+        match token.token_type {
+            TokenType::Error => true,
+            TokenType::EOF => self
+                .tokens
+                .iter()
+                .rev()
+                .nth(1)
+                .is_some_and(|token| token.token_type == TokenType::Error),
+            _ => false,
+        }
+    }
+
+    /// Discards tokens until the start of the next statement, or the `}`
+    /// closing the block the error was in.
+    fn synchronize(&mut self) {
+        // An error at the closing `}` itself, like a missing `;` before it,
+        // leaves the brace for `block` to consume.
+        if self.block_depth > 0 && self.check(TokenType::RightBrace) {
+            return;
+        }
+        self.advance();
+
+        while !self.is_at_end() {
+            if self.previous().token_type == TokenType::Semicolon {
+                return;
+            }
+
+            match self.peek().token_type {
+                TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return => return,
+                TokenType::RightBrace if self.block_depth > 0 => return,
+                _ => {
+                    self.advance();
+                }
+            }
         }
     }
 
@@ -268,3 +815,115 @@ impl Parser<'_> {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::scanner::Scanner;
+
+    #[test]
+    fn test_invalid_assignment_target_reports_without_aborting() {
+        let mut error_reporter = ErrorReporter::new();
+        let mut scanner = Scanner::new("(a) = 3; 1 + 2 = 3;".to_string(), &mut error_reporter);
+        scanner.scan_tokens();
+        let tokens = scanner.tokens;
+        let mut parser = Parser::new(tokens, &mut error_reporter);
+        let errors = parser.parse().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(
+            |error| error.kind == ParseErrorKind::InvalidAssignmentTarget
+                && error.token.token_type == TokenType::Equal
+        ));
+        assert!(error_reporter.had_error);
+    }
+
+    #[test]
+    fn test_too_many_arguments_reports_without_aborting() {
+        let arguments = vec!["1"; 256].join(", ");
+        let source = format!("f({arguments});");
+        let mut error_reporter = ErrorReporter::new();
+        let mut scanner = Scanner::new(source, &mut error_reporter);
+        scanner.scan_tokens();
+        let tokens = scanner.tokens;
+        let mut parser = Parser::new(tokens, &mut error_reporter);
+        let errors = parser.parse().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ParseErrorKind::TooManyArguments);
+        assert_eq!(errors[0].message, "Can't have more than 255 arguments.");
+        assert!(error_reporter.had_error);
+    }
+
+    #[test]
+    fn test_recovers_and_collects_every_syntax_error() {
+        let mut error_reporter = ErrorReporter::new();
+        let mut scanner = Scanner::new(
+            "var = 1;\nprint (1 + ;\nvar ok = 2;\nfun (a) {}\nprint ok;".to_string(),
+            &mut error_reporter,
+        );
+        scanner.scan_tokens();
+        let tokens = scanner.tokens;
+        let mut parser = Parser::new(tokens, &mut error_reporter);
+        let errors = parser.parse().unwrap_err();
+        let lines: Vec<u32> = errors.iter().map(|error| error.token.span.line).collect();
+        assert_eq!(lines, vec![1, 2, 4]);
+        assert_eq!(errors[1].kind, ParseErrorKind::ExpectedExpression);
+    }
+
+    #[test]
+    fn test_recovers_inside_function_and_method_bodies() {
+        for (source, expected) in [
+            ("fun f() {\n  var = 1;\n  print 2;\n}\nprint 3;", vec![2]),
+            (
+                "class A {\n  m() { print (; }\n  n() { return 1; }\n}",
+                vec![2],
+            ),
+            ("fun f() {\n  var = 1\n}\nprint (;", vec![2, 4]),
+            ("{ print 1 }\nprint 2;", vec![1]),
+        ] {
+            let mut error_reporter = ErrorReporter::new();
+            let mut scanner = Scanner::new(source.to_string(), &mut error_reporter);
+            scanner.scan_tokens();
+            let mut parser = Parser::new(scanner.tokens, &mut error_reporter);
+            let errors = parser.parse().unwrap_err();
+            let lines: Vec<u32> = errors.iter().map(|error| error.token.span.line).collect();
+            assert_eq!(lines, expected, "{source}");
+        }
+    }
+
+    #[test]
+    fn test_unclosed_paren_reports_error_at_end() {
+        let mut error_reporter = ErrorReporter::new();
+        let mut scanner = Scanner::new("(1 + \n\n5 + 5".to_string(), &mut error_reporter);
+        scanner.scan_tokens();
+        let tokens = scanner.tokens;
+        let mut parser = Parser::new(tokens, &mut error_reporter);
+        let errors = parser.parse().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ParseErrorKind::ExpectedToken);
+        assert_eq!(errors[0].token.token_type, TokenType::EOF);
+    }
+
+    #[test]
+    fn test_nodes_carry_source_spans() {
+        let source = "print -(1 + 2) * x;";
+        let mut error_reporter = ErrorReporter::new();
+        let mut scanner = Scanner::new(source.to_string(), &mut error_reporter);
+        scanner.scan_tokens();
+        let tokens = scanner.tokens;
+        let mut parser = Parser::new(tokens, &mut error_reporter);
+        let statements = parser.parse().unwrap();
+
+        let statement_span = statements[0].span();
+        assert_eq!(&source[statement_span.start..statement_span.end()], source);
+
+        let Stmt::Print { expression, .. } = &statements[0] else {
+            panic!("Expected print statement");
+        };
+        let Expr::Binary { left, .. } = expression else {
+            panic!("Expected binary expression");
+        };
+        let span = left.span();
+        assert_eq!(&source[span.start..span.end()], "-(1 + 2)");
+        assert_eq!((span.line, span.column), (1, 7));
+    }
+}
@@ -0,0 +1,482 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
+
+use super::{
+    error::{ResolveError, ResolveErrorKind},
+    error_reporter::{ErrorReporter, RESOLVE_ERROR},
+    expr::{Depth, Expr, Value, Visitor as ExprVisitor},
+    stmt::{FunctionDecl, Stmt, Visitor as StmtVisitor, Void},
+    token::Token,
+};
+
+#[derive(Copy, Clone, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// Walks the AST before it is interpreted and records in each variable node
+/// how many scopes up its binding lives.
+pub struct Resolver<'a> {
+    error_reporter: RefCell<&'a mut ErrorReporter>,
+    errors: RefCell<Vec<ResolveError>>,
+    // Each scope maps a variable name to whether its initializer has finished
+    // resolving.
+    scopes: RefCell<Vec<HashMap<String, bool>>>,
+    current_function: Cell<FunctionType>,
+    current_class: Cell<ClassType>,
+}
+
+impl Resolver<'_> {
+    pub fn new(error_reporter: &mut ErrorReporter) -> Resolver<'_> {
+        Resolver {
+            error_reporter: RefCell::new(error_reporter),
+            errors: RefCell::new(Vec::new()),
+            scopes: RefCell::new(Vec::new()),
+            current_function: Cell::new(FunctionType::None),
+            current_class: Cell::new(ClassType::None),
+        }
+    }
+
+    /// Resolves a whole program, returning every static error found.
+    pub fn resolve(&self, statements: &[Stmt]) -> Result<(), Vec<ResolveError>> {
+        self.resolve_stmts(statements);
+        let errors = self.errors.take();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn resolve_stmts(&self, statements: &[Stmt]) {
+        for statement in statements {
+            statement.accept(self);
+        }
+    }
+
+    fn resolve_expr(&self, expr: &Expr) {
+        expr.accept(self);
+    }
+
+    fn resolve_function(&self, declaration: &FunctionDecl, function_type: FunctionType) {
+        let enclosing_function = self.current_function.replace(function_type);
+
+        self.begin_scope();
+        for param in &declaration.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_stmts(&declaration.body);
+        self.end_scope();
+
+        self.current_function.set(enclosing_function);
+    }
+
+    fn resolve_local(&self, name: &Token, depth: &Depth) {
+        let resolved = self
+            .scopes
+            .borrow()
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(&name.lexeme));
+        depth.set(resolved);
+    }
+
+    fn begin_scope(&self) {
+        self.scopes.borrow_mut().push(HashMap::new());
+    }
+
+    fn end_scope(&self) {
+        self.scopes.borrow_mut().pop();
+    }
+
+    fn declare(&self, name: &Token) {
+        let mut scopes = self.scopes.borrow_mut();
+        let Some(scope) = scopes.last_mut() else {
+            return;
+        };
+
+        if scope.contains_key(&name.lexeme) {
+            self.error(
+                ResolveErrorKind::DuplicateVariable,
+                name,
+                "Already a variable with this name in this scope.",
+            );
+        }
+        scope.insert(name.lexeme.clone(), false);
+    }
+
+    fn define(&self, name: &Token) {
+        if let Some(scope) = self.scopes.borrow_mut().last_mut() {
+            scope.insert(name.lexeme.clone(), true);
+        }
+    }
+
+    fn error(&self, kind: ResolveErrorKind, token: &Token, message: &str) {
+        self.error_with_help(kind, token, message, None);
+    }
+
+    fn error_with_help(
+        &self,
+        kind: ResolveErrorKind,
+        token: &Token,
+        message: &str,
+        help: Option<&str>,
+    ) {
+        let mut diagnostic = ErrorReporter::token_diagnostic(RESOLVE_ERROR, token, message);
+        if let Some(help) = help {
+            diagnostic = diagnostic.with_help(help);
+        }
+        self.error_reporter.borrow_mut().report(diagnostic);
+        self.errors
+            .borrow_mut()
+            .push(ResolveError::new(kind, token, message));
+    }
+}
+
+impl ExprVisitor<Void> for Resolver<'_> {
+    fn visit_literal(&self, _value: &Option<Value>) -> Void {
+        Void
+    }
+
+    fn visit_binary(&self, left: &Expr, _operation: &Token, right: &Expr) -> Void {
+        self.resolve_expr(left);
+        self.resolve_expr(right);
+        Void
+    }
+
+    fn visit_grouping(&self, expression: &Expr) -> Void {
+        self.resolve_expr(expression);
+        Void
+    }
+
+    fn visit_unary(&self, _operator: &Token, right: &Expr) -> Void {
+        self.resolve_expr(right);
+        Void
+    }
+
+    fn visit_ternary(&self, condition: &Expr, then_branch: &Expr, else_branch: &Expr) -> Void {
+        self.resolve_expr(condition);
+        self.resolve_expr(then_branch);
+        self.resolve_expr(else_branch);
+        Void
+    }
+
+    fn visit_variable(&self, token: &Token, depth: &Depth) -> Void {
+        let in_own_initializer = self
+            .scopes
+            .borrow()
+            .last()
+            .and_then(|scope| scope.get(&token.lexeme))
+            == Some(&false);
+        if in_own_initializer {
+            self.error_with_help(
+                ResolveErrorKind::ReadInOwnInitializer,
+                token,
+                "Can't read local variable in its own initializer.",
+                Some("use a different name, or read the outer variable before shadowing it"),
+            );
+        }
+
+        self.resolve_local(token, depth);
+        Void
+    }
+
+    fn visit_assign(&self, name: &Token, value: &Expr, depth: &Depth) -> Void {
+        self.resolve_expr(value);
+        self.resolve_local(name, depth);
+        Void
+    }
+
+    fn visit_logical(&self, left: &Expr, _operator: &Token, right: &Expr) -> Void {
+        self.resolve_expr(left);
+        self.resolve_expr(right);
+        Void
+    }
+
+    fn visit_call(&self, callee: &Expr, _paren: &Token, arguments: &[Expr]) -> Void {
+        self.resolve_expr(callee);
+        for argument in arguments {
+            self.resolve_expr(argument);
+        }
+        Void
+    }
+
+    fn visit_get(&self, object: &Expr, _name: &Token) -> Void {
+        self.resolve_expr(object);
+        Void
+    }
+
+    fn visit_set(&self, object: &Expr, _name: &Token, value: &Expr) -> Void {
+        self.resolve_expr(value);
+        self.resolve_expr(object);
+        Void
+    }
+
+    fn visit_this(&self, keyword: &Token, depth: &Depth) -> Void {
+        if self.current_class.get() == ClassType::None {
+            self.error(
+                ResolveErrorKind::ThisOutsideClass,
+                keyword,
+                "Can't use 'this' outside of a class.",
+            );
+            return Void;
+        }
+
+        self.resolve_local(keyword, depth);
+        Void
+    }
+
+    fn visit_super(&self, keyword: &Token, _method: &Token, depth: &Depth) -> Void {
+        match self.current_class.get() {
+            ClassType::None => self.error(
+                ResolveErrorKind::SuperOutsideClass,
+                keyword,
+                "Can't use 'super' outside of a class.",
+            ),
+            ClassType::Class => self.error(
+                ResolveErrorKind::SuperWithoutSuperclass,
+                keyword,
+                "Can't use 'super' in a class with no superclass.",
+            ),
+            ClassType::Subclass => {}
+        }
+
+        self.resolve_local(keyword, depth);
+        Void
+    }
+}
+
+impl StmtVisitor<Void> for Resolver<'_> {
+    fn visit_expression(&self, expr: &Expr) -> Void {
+        self.resolve_expr(expr);
+        Void
+    }
+
+    fn visit_print(&self, expr: &Expr) -> Void {
+        self.resolve_expr(expr);
+        Void
+    }
+
+    fn visit_var(&self, name: &Token, initializer: &Option<Expr>) -> Void {
+        self.declare(name);
+        if let Some(initializer) = initializer {
+            self.resolve_expr(initializer);
+        }
+        self.define(name);
+        Void
+    }
+
+    fn visit_block(&self, statements: &[Stmt]) -> Void {
+        self.begin_scope();
+        self.resolve_stmts(statements);
+        self.end_scope();
+        Void
+    }
+
+    fn visit_if(
+        &self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: &Option<Box<Stmt>>,
+    ) -> Void {
+        self.resolve_expr(condition);
+        then_branch.accept(self);
+        if let Some(else_branch) = else_branch {
+            else_branch.accept(self);
+        }
+        Void
+    }
+
+    fn visit_while(&self, condition: &Expr, body: &Stmt) -> Void {
+        self.resolve_expr(condition);
+        body.accept(self);
+        Void
+    }
+
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> Void {
+        // Define the name eagerly so the function can refer to itself.
+        self.declare(&declaration.name);
+        self.define(&declaration.name);
+
+        self.resolve_function(declaration, FunctionType::Function);
+        Void
+    }
+
+    fn visit_return(&self, keyword: &Token, value: &Option<Expr>) -> Void {
+        if self.current_function.get() == FunctionType::None {
+            self.error(
+                ResolveErrorKind::ReturnAtTopLevel,
+                keyword,
+                "Can't return from top-level code.",
+            );
+        }
+
+        if let Some(value) = value {
+            if self.current_function.get() == FunctionType::Initializer {
+                self.error_with_help(
+                    ResolveErrorKind::ReturnValueFromInitializer,
+                    keyword,
+                    "Can't return a value from an initializer.",
+                    Some("initializers always return 'this'; use a bare 'return;'"),
+                );
+            }
+
+            self.resolve_expr(value);
+        }
+        Void
+    }
+
+    fn visit_class(
+        &self,
+        name: &Token,
+        superclass: &Option<Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) -> Void {
+        let enclosing_class = self.current_class.replace(ClassType::Class);
+
+        self.declare(name);
+        self.define(name);
+
+        if let Some(superclass) = superclass {
+            if let Expr::Variable {
+                name: superclass_name,
+                ..
+            } = superclass
+            {
+                if superclass_name.lexeme == name.lexeme {
+                    self.error(
+                        ResolveErrorKind::InheritFromSelf,
+                        superclass_name,
+                        "A class can't inherit from itself.",
+                    );
+                }
+            }
+
+            self.current_class.set(ClassType::Subclass);
+            self.resolve_expr(superclass);
+
+            self.begin_scope();
+            if let Some(scope) = self.scopes.borrow_mut().last_mut() {
+                scope.insert("super".to_string(), true);
+            }
+        }
+
+        self.begin_scope();
+        if let Some(scope) = self.scopes.borrow_mut().last_mut() {
+            scope.insert("this".to_string(), true);
+        }
+
+        for method in methods {
+            let function_type = if method.name.lexeme == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_function(method, function_type);
+        }
+
+        self.end_scope();
+
+        if superclass.is_some() {
+            self.end_scope();
+        }
+
+        self.current_class.set(enclosing_class);
+        Void
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_utils::tests::helper_create_stmts_from_string;
+
+    fn helper_resolve_errors(source: &str) -> Vec<ResolveErrorKind> {
+        let statements = helper_create_stmts_from_string(source);
+        let mut error_reporter = ErrorReporter::new();
+        match Resolver::new(&mut error_reporter).resolve(&statements) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.iter().map(|error| error.kind).collect(),
+        }
+    }
+
+    #[test]
+    fn test_reading_local_in_own_initializer_is_an_error() {
+        assert_eq!(
+            helper_resolve_errors("var a = 1; { var a = a; }"),
+            vec![ResolveErrorKind::ReadInOwnInitializer]
+        );
+    }
+
+    #[test]
+    fn test_global_may_reference_itself_in_initializer() {
+        assert!(helper_resolve_errors("var a = 1; var a = a;").is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_local_declaration_is_an_error() {
+        assert_eq!(
+            helper_resolve_errors("{ var a = 1; var a = 2; }"),
+            vec![ResolveErrorKind::DuplicateVariable]
+        );
+    }
+
+    #[test]
+    fn test_return_at_top_level_is_an_error() {
+        assert_eq!(
+            helper_resolve_errors("return 1;"),
+            vec![ResolveErrorKind::ReturnAtTopLevel]
+        );
+    }
+
+    #[test]
+    fn test_this_outside_class_is_an_error() {
+        assert_eq!(
+            helper_resolve_errors("fun f() { return this; }"),
+            vec![ResolveErrorKind::ThisOutsideClass]
+        );
+    }
+
+    #[test]
+    fn test_returning_value_from_initializer_is_an_error() {
+        assert_eq!(
+            helper_resolve_errors("class A { init() { return 1; } }"),
+            vec![ResolveErrorKind::ReturnValueFromInitializer]
+        );
+        assert!(helper_resolve_errors("class A { init() { return; } }").is_empty());
+    }
+
+    #[test]
+    fn test_class_inheriting_from_itself_is_an_error() {
+        assert_eq!(
+            helper_resolve_errors("class A < A {}"),
+            vec![ResolveErrorKind::InheritFromSelf]
+        );
+    }
+
+    #[test]
+    fn test_super_without_superclass_is_an_error() {
+        assert_eq!(
+            helper_resolve_errors("class A { f() { super.f(); } }"),
+            vec![ResolveErrorKind::SuperWithoutSuperclass]
+        );
+        assert_eq!(
+            helper_resolve_errors("fun f() { super.f(); }"),
+            vec![ResolveErrorKind::SuperOutsideClass]
+        );
+    }
+}
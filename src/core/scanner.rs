@@ -1,20 +1,37 @@
 use std::collections::HashMap;
 
-use super::{error_reporter::ErrorReporter, token::*, token_type::*};
+use super::{
+    error::{ScanError, ScanErrorKind},
+    error_reporter::ErrorReporter,
+    span::Span,
+    token::*,
+    token_type::*,
+};
 
 pub struct Scanner<'a> {
     source: String,
     pub tokens: Vec<Token>,
-    start: u32,
-    current: u32,
+    pub errors: Vec<ScanError>,
+    // Byte offsets into `source`.
+    start: usize,
+    current: usize,
     line: u32,
+    // Byte offset where the current line begins, used to compute columns.
+    line_start: usize,
+    start_line: u32,
+    start_column: u32,
+    // Trivia waiting for the next token, and trivia after the last token on
+    // its line, which is attached once the line ends.
+    leading_trivia: Vec<Trivia>,
+    trailing_trivia: Vec<Trivia>,
+    on_token_line: bool,
 
     error_reporter: &'a mut ErrorReporter,
     reserved_keywords: HashMap<String, TokenType>,
 }
 
 impl Scanner<'_> {
-    pub fn new(source: String, error_reporter: &mut ErrorReporter) -> Scanner {
+    pub fn new(source: String, error_reporter: &mut ErrorReporter) -> Scanner<'_> {
         let reserved_keywords = HashMap::from([
             ("and".to_string(), TokenType::And),
             ("class".to_string(), TokenType::Class),
@@ -36,25 +53,68 @@ impl Scanner<'_> {
         Scanner {
             source,
             tokens: Vec::new(),
+            errors: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
-            error_reporter: error_reporter,
-            reserved_keywords: reserved_keywords,
+            line_start: 0,
+            start_line: 1,
+            start_column: 1,
+            leading_trivia: Vec::new(),
+            trailing_trivia: Vec::new(),
+            on_token_line: false,
+            error_reporter,
+            reserved_keywords,
         }
     }
 
     pub fn scan_tokens(&mut self) {
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column_at(self.start);
             self.scan_token();
         }
+
+        self.end_token_line();
+        let span = Span::new(self.current, 0, self.line, self.column_at(self.current));
+        let mut eof = Token::new(TokenType::EOF, "".to_string(), None, span);
+        eof.leading_trivia = std::mem::take(&mut self.leading_trivia).into();
+        self.tokens.push(eof);
+    }
+
+    /// Spans of the `//` and `/* */` comments in the scanned tokens' trivia.
+    pub fn comments(&self) -> Vec<Span> {
         self.tokens
-            .push(Token::new(TokenType::EOF, "".to_string(), None, self.line));
+            .iter()
+            .flat_map(|token| {
+                token
+                    .leading_trivia
+                    .iter()
+                    .chain(token.trailing_trivia.iter())
+            })
+            .filter(|trivia| {
+                matches!(
+                    trivia.kind,
+                    TriviaKind::LineComment | TriviaKind::BlockComment
+                )
+            })
+            .map(|trivia| trivia.span)
+            .collect()
     }
 
     fn is_at_end(&self) -> bool {
-        self.current >= self.source.len() as u32
+        self.current >= self.source.len()
+    }
+
+    fn column_at(&self, offset: usize) -> u32 {
+        self.source[self.line_start..offset].chars().count() as u32 + 1
+    }
+
+    // Called after consuming a '\n'.
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
     }
 
     fn scan_token(&mut self) {
@@ -110,27 +170,41 @@ impl Scanner<'_> {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                    self.add_trivia(TriviaKind::LineComment);
                 } else if self.r#match('*') {
                     // C style comment `/*` goes until `*/`
-                    while self.peek() != '*' && self.peek_next() != '/' {
-                        self.advance();
-
+                    loop {
                         if self.is_at_end() {
-                            self.error_reporter
-                                .error(self.line, &"Unterminated comment".to_string());
+                            self.error(ScanErrorKind::UnterminatedComment, "Unterminated comment.");
                             break;
                         }
-                    }
 
-                    // advance twice to move past `*/`
-                    self.advance();
-                    self.advance();
+                        if self.peek() == '*' && self.peek_next() == '/' {
+                            // advance twice to move past `*/`
+                            self.advance();
+                            self.advance();
+                            break;
+                        }
+
+                        if self.advance() == '\n' {
+                            self.new_line();
+                        }
+                    }
+                    self.add_trivia(TriviaKind::BlockComment);
                 } else {
                     self.add_token(TokenType::Slash, None);
                 }
             }
-            ' ' | '\r' | '\t' => {}
-            '\n' => self.line += 1,
+            ' ' | '\r' | '\t' => {
+                while matches!(self.peek(), ' ' | '\r' | '\t') {
+                    self.advance();
+                }
+                self.add_trivia(TriviaKind::Whitespace);
+            }
+            '\n' => {
+                self.new_line();
+                self.add_trivia(TriviaKind::Newline);
+            }
             '"' => self.string(),
             unexpected => {
                 if self.is_digit(c) {
@@ -138,8 +212,11 @@ impl Scanner<'_> {
                 } else if self.is_alpha(c) {
                     self.identifier();
                 } else {
-                    self.error_reporter
-                        .error(self.line, &format!("Unexpected character {unexpected}"));
+                    self.error(
+                        ScanErrorKind::UnexpectedCharacter,
+                        &format!("Unexpected character {unexpected}"),
+                    );
+                    self.add_trivia(TriviaKind::Skipped);
                 }
             }
         }
@@ -150,12 +227,12 @@ impl Scanner<'_> {
             self.advance();
         }
 
-        let text = &self.source[self.start as usize..self.current as usize];
+        let text = &self.source[self.start..self.current];
         let token_type = self
             .reserved_keywords
             .get(text)
             .unwrap_or(&TokenType::Identifier);
-        self.add_token(token_type.clone(), None)
+        self.add_token(*token_type, None)
     }
 
     fn number(&mut self) {
@@ -173,63 +250,50 @@ impl Scanner<'_> {
 
         self.add_token(
             TokenType::Number,
-            Some(self.source[self.start as usize..self.current as usize].to_string()),
+            Some(self.source[self.start..self.current].to_string()),
         );
     }
 
     fn string(&mut self) {
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+            if self.advance() == '\n' {
+                self.new_line();
             }
-            self.advance();
         }
 
         if self.is_at_end() {
-            self.error_reporter
-                .error(self.line, &"Unterminated string.".to_string());
+            self.error(ScanErrorKind::UnterminatedString, "Unterminated string.");
+            self.add_token(TokenType::Error, None);
+            return;
         }
 
         self.advance();
 
-        let value = &self.source[self.start as usize + 1..self.current as usize - 1];
+        let value = &self.source[self.start + 1..self.current - 1];
         self.add_token(TokenType::String, Some(value.to_string()));
     }
 
     fn r#match(&mut self, expected: char) -> bool {
-        if self.is_at_end() {
-            return false;
-        }
-
-        if self.source.chars().nth(self.current as usize).unwrap() != expected {
+        if self.is_at_end() || self.peek() != expected {
             return false;
         }
 
-        self.current += 1;
-        return true;
+        self.current += expected.len_utf8();
+        true
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            return '\0';
-        }
-
-        self.source.chars().nth(self.current as usize).unwrap()
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() as u32 {
-            return '\0';
-        }
-        return self
-            .source
-            .chars()
-            .nth((self.current + 1) as usize)
-            .unwrap();
+        let mut chars = self.source[self.current..].chars();
+        chars.next();
+        chars.next().unwrap_or('\0')
     }
 
     fn is_alpha(&self, c: char) -> bool {
-        (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_'
+        c.is_ascii_lowercase() || c.is_ascii_uppercase() || c == '_'
     }
 
     fn is_alpha_numeric(&self, c: char) -> bool {
@@ -237,20 +301,184 @@ impl Scanner<'_> {
     }
 
     fn is_digit(&self, c: char) -> bool {
-        c >= '0' && c <= '9'
+        c.is_ascii_digit()
     }
 
     fn advance(&mut self) -> char {
-        self.current += 1;
-        self.source
-            .chars()
-            .nth((self.current - 1) as usize)
-            .unwrap()
+        let c = self.peek();
+        self.current += c.len_utf8();
+        c
+    }
+
+    // Span of the lexeme scanned so far.
+    fn lexeme_span(&self) -> Span {
+        Span::new(
+            self.start,
+            self.current - self.start,
+            self.start_line,
+            self.start_column,
+        )
     }
 
     fn add_token(&mut self, token_type: TokenType, literal: Option<String>) {
-        let text = &self.source[self.start as usize..self.current as usize];
-        self.tokens
-            .push(Token::new(token_type, text.to_string(), literal, self.line));
+        self.end_token_line();
+        let text = &self.source[self.start..self.current];
+        let mut token = Token::new(token_type, text.to_string(), literal, self.lexeme_span());
+        token.leading_trivia = std::mem::take(&mut self.leading_trivia).into();
+        self.tokens.push(token);
+        self.on_token_line = true;
+    }
+
+    /// Records the lexeme scanned so far as trivia. Trivia on the line of the
+    /// last token trails it; a newline, and everything after, leads the next.
+    fn add_trivia(&mut self, kind: TriviaKind) {
+        let text = &self.source[self.start..self.current];
+        let ends_line = kind == TriviaKind::Newline || text.contains('\n');
+        let trivia = Trivia {
+            kind,
+            text: text.to_string(),
+            span: self.lexeme_span(),
+        };
+        if self.on_token_line && kind != TriviaKind::Newline {
+            self.trailing_trivia.push(trivia);
+        } else {
+            self.leading_trivia.push(trivia);
+        }
+        if ends_line {
+            self.end_token_line();
+        }
+    }
+
+    /// Attaches the trailing trivia collected so far to the last token.
+    fn end_token_line(&mut self) {
+        if let (true, Some(token)) = (self.on_token_line, self.tokens.last_mut()) {
+            token.trailing_trivia = std::mem::take(&mut self.trailing_trivia).into();
+        }
+        self.on_token_line = false;
+    }
+
+    fn error(&mut self, kind: ScanErrorKind, message: &str) {
+        let span = self.lexeme_span();
+        self.error_reporter.scan_error(span, message);
+        self.errors.push(ScanError::new(kind, span, message));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn helper_scan(source: &str) -> Vec<Token> {
+        let mut error_reporter = ErrorReporter::new();
+        let mut scanner = Scanner::new(source.to_string(), &mut error_reporter);
+        scanner.scan_tokens();
+        let tokens = scanner.tokens;
+        assert!(!error_reporter.had_error);
+        tokens
+    }
+
+    #[test]
+    fn test_token_spans_track_offset_column_and_length() {
+        let tokens = helper_scan("var answer = 42;\n  print answer;");
+        let answer = &tokens[1];
+        assert_eq!(answer.span, Span::new(4, 6, 1, 5));
+
+        let print = &tokens[5];
+        assert_eq!(print.lexeme, "print");
+        assert_eq!(print.span, Span::new(19, 5, 2, 3));
+    }
+
+    #[test]
+    fn test_columns_after_multi_line_string() {
+        let tokens = helper_scan("\"one\ntwo\" + x;");
+        assert_eq!(tokens[0].span, Span::new(0, 9, 1, 1));
+        let x = &tokens[2];
+        assert_eq!(x.lexeme, "x");
+        assert_eq!((x.span.line, x.span.column), (2, 8));
+    }
+
+    #[test]
+    fn test_columns_after_block_comment() {
+        let tokens = helper_scan("/* a\n * b */ y");
+        assert_eq!(tokens[0].lexeme, "y");
+        assert_eq!((tokens[0].span.line, tokens[0].span.column), (2, 9));
+    }
+
+    #[test]
+    fn test_spans_are_byte_offsets_with_character_columns() {
+        let source = "\"héllo\" z";
+        let tokens = helper_scan(source);
+        let z = &tokens[1];
+        assert_eq!(&source[z.span.start..z.span.end()], "z");
+        assert_eq!(z.span.column, 9);
+    }
+
+    #[test]
+    fn test_errors_carry_kind_and_span() {
+        let mut error_reporter = ErrorReporter::new();
+        let mut scanner = Scanner::new("x @ \"open".to_string(), &mut error_reporter);
+        scanner.scan_tokens();
+        let kinds: Vec<ScanErrorKind> = scanner.errors.iter().map(|error| error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ScanErrorKind::UnexpectedCharacter,
+                ScanErrorKind::UnterminatedString
+            ]
+        );
+        assert_eq!(scanner.errors[0].span, Span::new(2, 1, 1, 3));
+        assert_eq!(scanner.errors[1].span, Span::new(4, 5, 1, 5));
+    }
+
+    #[test]
+    fn test_unterminated_string_becomes_error_token() {
+        let mut error_reporter = ErrorReporter::quiet();
+        let mut scanner = Scanner::new("print \"open".to_string(), &mut error_reporter);
+        scanner.scan_tokens();
+        let tokens = scanner.tokens;
+        assert_eq!(tokens[1].token_type, TokenType::Error);
+        assert_eq!(tokens[1].lexeme, "\"open");
+        assert_eq!(tokens[2].token_type, TokenType::EOF);
+    }
+
+    #[test]
+    fn test_comments_are_recorded_but_not_tokens() {
+        let mut error_reporter = ErrorReporter::new();
+        let source = "// a\nprint /* b\n */ 1;";
+        let mut scanner = Scanner::new(source.to_string(), &mut error_reporter);
+        scanner.scan_tokens();
+        let comments: Vec<&str> = scanner
+            .comments()
+            .iter()
+            .map(|span| &source[span.start..span.end()])
+            .collect();
+        assert_eq!(comments, ["// a", "/* b\n */"]);
+        assert_eq!(scanner.comments()[1].line, 2);
+        assert_eq!(scanner.tokens.len(), 4);
+    }
+
+    #[test]
+    fn test_trivia_is_split_at_the_end_of_the_line() {
+        let tokens = helper_scan("var a; // a\n  /* b */ print\t1;");
+        let texts = |trivia: &[Trivia]| -> Vec<String> {
+            trivia.iter().map(|trivia| trivia.text.clone()).collect()
+        };
+        assert_eq!(texts(&tokens[2].trailing_trivia), [" ", "// a"]);
+        assert_eq!(
+            tokens[3]
+                .leading_trivia
+                .iter()
+                .map(|trivia| trivia.kind)
+                .collect::<Vec<_>>(),
+            [
+                TriviaKind::Newline,
+                TriviaKind::Whitespace,
+                TriviaKind::BlockComment,
+                TriviaKind::Whitespace
+            ]
+        );
+        assert_eq!(tokens[3].full_text(), "\n  /* b */ print\t");
+        let text: String = tokens.iter().map(Token::full_text).collect();
+        assert_eq!(text, "var a; // a\n  /* b */ print\t1;");
     }
 }
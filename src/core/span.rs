@@ -0,0 +1,34 @@
+/// A range of source text. Offsets are in bytes so a span can slice the
+/// source directly; `line` and `column` are 1-based and point at the first
+/// character, with columns counted in characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub length: usize,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Span {
+    pub fn new(start: usize, length: usize, line: u32, column: u32) -> Span {
+        Span {
+            start,
+            length,
+            line,
+            column,
+        }
+    }
+
+    /// Byte offset just past the last character.
+    pub fn end(&self) -> usize {
+        self.start + self.length
+    }
+
+    /// Returns a span starting where `self` starts and ending where `other` ends.
+    pub fn to(&self, other: Span) -> Span {
+        Span {
+            length: other.end().saturating_sub(self.start),
+            ..*self
+        }
+    }
+}
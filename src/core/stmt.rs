@@ -1,30 +1,141 @@
-use super::{
-    expr::Expr,
-    token::Token
-};
+use std::rc::Rc;
+
+use super::{expr::Expr, span::Span, token::Token};
 
 pub struct Void;
 
+/// Which clauses the `for` loop a `while` was desugared from had, so the
+/// loop can be printed back as written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ForClauses {
+    /// The loop is the second statement of a block that runs the
+    /// initializer first.
+    pub initializer: bool,
+    /// Without one, the condition is a `true` literal.
+    pub condition: bool,
+    /// The body is a block whose second statement is the increment.
+    pub increment: bool,
+}
+
+/// A `fun` declaration. It is reference counted so the functions created
+/// from it at runtime can share the body instead of copying it.
+#[derive(Debug)]
+pub struct FunctionDecl {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+    /// From the `{` to the `}` around the body.
+    pub body_span: Span,
+    pub span: Span,
+}
+
+/// A statement node. Every node carries the `Span` of source text it was
+/// parsed from.
+#[derive(Debug)]
 pub enum Stmt {
-    Expression(Expr),
-    Print(Expr),
+    Expression {
+        expression: Expr,
+        span: Span,
+    },
+    Print {
+        expression: Expr,
+        span: Span,
+    },
     Var {
         name: Token,
         initializer: Option<Expr>,
-    }
+        span: Span,
+    },
+    Block {
+        statements: Vec<Stmt>,
+        span: Span,
+    },
+    If {
+        condition: Expr,
+        then_branch: Box<Stmt>,
+        else_branch: Option<Box<Stmt>>,
+        span: Span,
+    },
+    While {
+        condition: Expr,
+        body: Box<Stmt>,
+        /// Set when the parser built this loop from a `for`.
+        for_clauses: Option<ForClauses>,
+        span: Span,
+    },
+    Function(Rc<FunctionDecl>),
+    Return {
+        keyword: Token,
+        value: Option<Expr>,
+        span: Span,
+    },
+    Class {
+        name: Token,
+        superclass: Option<Expr>,
+        methods: Vec<Rc<FunctionDecl>>,
+        /// From the `{` to the `}` around the methods.
+        body_span: Span,
+        span: Span,
+    },
 }
 
 impl Stmt {
     pub fn accept<T>(&self, visitor: &dyn Visitor<T>) -> T {
         match self {
-            Stmt::Expression(expr) => visitor.visit_expression(expr),
-            Stmt::Print(expr) => visitor.visit_print(expr),
-            Stmt::Var { name, initializer } => visitor.visit_var(name, initializer)
+            Stmt::Expression { expression, .. } => visitor.visit_expression(expression),
+            Stmt::Print { expression, .. } => visitor.visit_print(expression),
+            Stmt::Var {
+                name, initializer, ..
+            } => visitor.visit_var(name, initializer),
+            Stmt::Block { statements, .. } => visitor.visit_block(statements),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => visitor.visit_if(condition, then_branch, else_branch),
+            Stmt::While {
+                condition, body, ..
+            } => visitor.visit_while(condition, body),
+            Stmt::Function(declaration) => visitor.visit_function(declaration),
+            Stmt::Return { keyword, value, .. } => visitor.visit_return(keyword, value),
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => visitor.visit_class(name, superclass, methods),
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Function(declaration) => declaration.span,
+            Stmt::Expression { span, .. }
+            | Stmt::Print { span, .. }
+            | Stmt::Var { span, .. }
+            | Stmt::Block { span, .. }
+            | Stmt::If { span, .. }
+            | Stmt::While { span, .. }
+            | Stmt::Return { span, .. }
+            | Stmt::Class { span, .. } => *span,
         }
     }
 }
+
 pub trait Visitor<T> {
     fn visit_expression(&self, expr: &Expr) -> T;
     fn visit_print(&self, expr: &Expr) -> T;
     fn visit_var(&self, name: &Token, initializer: &Option<Expr>) -> T;
+    fn visit_block(&self, statements: &[Stmt]) -> T;
+    fn visit_if(&self, condition: &Expr, then_branch: &Stmt, else_branch: &Option<Box<Stmt>>) -> T;
+    fn visit_while(&self, condition: &Expr, body: &Stmt) -> T;
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> T;
+    fn visit_return(&self, keyword: &Token, value: &Option<Expr>) -> T;
+    fn visit_class(
+        &self,
+        name: &Token,
+        superclass: &Option<Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) -> T;
 }
@@ -1,7 +1,14 @@
 #[cfg(test)]
 pub mod tests {
     use crate::core::{
-        error_reporter::ErrorReporter, expr::Expr, parser::Parser, scanner::Scanner, stmt::Stmt,
+        error::Result,
+        error_reporter::ErrorReporter,
+        expr::{Expr, Value},
+        interpreter::Interpreter,
+        parser::Parser,
+        resolver::Resolver,
+        scanner::Scanner,
+        stmt::Stmt,
     };
 
     pub fn helper_create_expr_from_string(expression: &str) -> Expr {
@@ -12,9 +19,37 @@ pub mod tests {
         let mut parser = Parser::new(tokens, &mut error_reporter);
         let stmt = parser.parse().unwrap();
         let expr = match &stmt[0] {
-            Stmt::Expression(expr) => expr,
+            Stmt::Expression { expression, .. } => expression,
             _ => panic!("Expected expression"),
         };
         expr.clone()
     }
+
+    pub fn helper_create_stmts_from_string(source: &str) -> Vec<Stmt> {
+        let mut error_reporter = ErrorReporter::new();
+        let mut scanner = Scanner::new(source.to_string(), &mut error_reporter);
+        scanner.scan_tokens();
+        let tokens = scanner.tokens;
+        let mut parser = Parser::new(tokens, &mut error_reporter);
+        parser.parse().unwrap()
+    }
+
+    pub fn helper_resolve_stmts(statements: &[Stmt]) {
+        let mut error_reporter = ErrorReporter::new();
+        Resolver::new(&mut error_reporter)
+            .resolve(statements)
+            .unwrap();
+    }
+
+    /// Runs `source` on a fresh interpreter, then evaluates `expression` in
+    /// the global scope it left behind.
+    pub fn helper_run_and_eval(source: &str, expression: &str) -> Result<Value> {
+        let statements = helper_create_stmts_from_string(source);
+        helper_resolve_stmts(&statements);
+        let interpreter = Interpreter::new();
+        for statement in &statements {
+            interpreter.execute(statement).unwrap();
+        }
+        interpreter.evalute(&helper_create_expr_from_string(expression))
+    }
 }
@@ -1,21 +1,77 @@
-use super::token_type::TokenType;
+use std::{fmt::Display, rc::Rc};
+
+use super::{span::Span, token_type::TokenType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    /// A run of spaces, tabs and carriage returns.
+    Whitespace,
+    Newline,
+    LineComment,
+    BlockComment,
+    /// Characters the scanner reported as errors, like an unexpected
+    /// character.
+    Skipped,
+}
+
+/// Source text between tokens that the parser does not see.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub span: Span,
+}
 
 #[derive(Debug, Clone)]
-#[allow(dead_code)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
     literal: Option<String>,
-    pub line: u32,
+    pub span: Span,
+    /// Trivia between the end of the previous token's line and this token.
+    pub leading_trivia: Rc<[Trivia]>,
+    /// Trivia after this token up to, but not including, the end of its line.
+    pub trailing_trivia: Rc<[Trivia]>,
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, lexeme: String, literal: Option<String>, line: u32) -> Token {
+    pub fn new(
+        token_type: TokenType,
+        lexeme: String,
+        literal: Option<String>,
+        span: Span,
+    ) -> Token {
         Token {
             token_type,
             lexeme,
             literal,
-            line,
+            span,
+            leading_trivia: Rc::new([]),
+            trailing_trivia: Rc::new([]),
+        }
+    }
+
+    /// The token's text with its leading and trailing trivia, exactly as it
+    /// appears in the source.
+    pub fn full_text(&self) -> String {
+        let mut text = String::new();
+        for trivia in self.leading_trivia.iter() {
+            text.push_str(&trivia.text);
+        }
+        text.push_str(&self.lexeme);
+        for trivia in self.trailing_trivia.iter() {
+            text.push_str(&trivia.text);
+        }
+        text
+    }
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.token_type, self.lexeme)?;
+        if let Some(literal) = &self.literal {
+            write!(f, " {}", literal)?;
         }
+        Ok(())
     }
 }
@@ -50,11 +50,14 @@ pub enum TokenType {
     Var,
     While,
 
+    /// Text the scanner reported as an error but could not skip, like an
+    /// unterminated string running to the end of the input.
+    Error,
     EOF,
 }
 
 impl fmt::Display for TokenType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self)
+        write!(f, "{:?}", self)
     }
 }
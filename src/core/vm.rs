@@ -0,0 +1,795 @@
+pub mod chunk;
+pub mod compiler;
+pub mod value;
+
+use std::{cell::RefCell, collections::HashMap, io::Write, rc::Rc};
+
+use self::{
+    compiler::Compiler,
+    value::{BoundMethod, Class, Closure, Function, Instance, Upvalue, Value},
+};
+use super::{
+    error::{Result, RuntimeError, RuntimeErrorKind, StackFrame},
+    error_reporter::ErrorReporter,
+    interpreter::MAX_CALL_DEPTH,
+    span::Span,
+    stmt::Stmt,
+};
+
+struct CallFrame {
+    closure: Rc<Closure>,
+    ip: usize,
+    // Index of the frame's slot zero on the VM stack.
+    slots: usize,
+    // Shown in stack traces. Initializers run by calling a class are named
+    // after the class, as in the tree-walker.
+    name: Rc<str>,
+    call_site: Span,
+}
+
+/// A bytecode backend and an alternative to the tree-walking `Interpreter`:
+/// programs are compiled into `Chunk`s of instructions that run on a value
+/// stack, printing exactly what the tree-walker would.
+pub struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<Rc<str>, Value>,
+    // Upvalues still pointing into the stack.
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    out: Box<dyn Write>,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Vm::with_output(std::io::stdout())
+    }
+
+    /// Creates a VM whose `print` statements write to `out`.
+    pub fn with_output(out: impl Write + 'static) -> Self {
+        Vm {
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: HashMap::new(),
+            open_upvalues: Vec::new(),
+            out: Box::new(out),
+        }
+    }
+
+    /// Compiles and runs `statements`, stopping at and reporting the first
+    /// runtime error.
+    pub fn interpret(
+        &mut self,
+        statements: &[Stmt],
+        error_reporter: &mut ErrorReporter,
+    ) -> Result<()> {
+        let function = Compiler::new().compile(statements);
+        self.execute(function, error_reporter)
+    }
+
+    /// Like `interpret`, but also prints the value of every top-level
+    /// expression statement, as the REPL does.
+    pub fn interpret_echoing(
+        &mut self,
+        statements: &[Stmt],
+        error_reporter: &mut ErrorReporter,
+    ) -> Result<()> {
+        let function = Compiler::new().compile_echoing(statements);
+        self.execute(function, error_reporter)
+    }
+
+    /// The global variables defined so far.
+    pub fn globals(&self) -> impl Iterator<Item = (&Rc<str>, &Value)> {
+        self.globals.iter()
+    }
+
+    fn execute(&mut self, function: Function, error_reporter: &mut ErrorReporter) -> Result<()> {
+        let closure = Rc::new(Closure {
+            function: Rc::new(function),
+            upvalues: Vec::new(),
+        });
+        self.stack.push(Value::Closure(closure.clone()));
+        self.frames.push(CallFrame {
+            name: closure.function.name.clone(),
+            closure,
+            ip: 0,
+            slots: 0,
+            call_site: Span::default(),
+        });
+
+        let result = self.run();
+        if let Err(error) = &result {
+            error_reporter.runtime_error(error);
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+    }
+
+    fn run(&mut self) -> Result<()> {
+        use chunk::OpCode;
+
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let op = frame.closure.function.chunk.code[frame.ip];
+            frame.ip += 1;
+
+            match op {
+                OpCode::Constant(index) => {
+                    let constant = self.constant(index);
+                    self.stack.push(constant);
+                }
+                OpCode::Nil => self.stack.push(Value::Nil),
+                OpCode::True => self.stack.push(Value::Bool(true)),
+                OpCode::False => self.stack.push(Value::Bool(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal(slot) => {
+                    let value = self.stack[self.slot(slot)].clone();
+                    self.stack.push(value);
+                }
+                OpCode::SetLocal(slot) => {
+                    let slot = self.slot(slot);
+                    self.stack[slot] = self.peek(0).clone();
+                }
+                OpCode::GetGlobal(index) => {
+                    let name = self.name_constant(index);
+                    match self.globals.get(&name) {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => return Err(self.undefined_variable(&name)),
+                    }
+                }
+                OpCode::DefineGlobal(index) => {
+                    let name = self.name_constant(index);
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+                OpCode::SetGlobal(index) => {
+                    let name = self.name_constant(index);
+                    let value = self.peek(0).clone();
+                    match self.globals.get_mut(&name) {
+                        Some(slot) => *slot = value,
+                        None => return Err(self.undefined_variable(&name)),
+                    }
+                }
+                OpCode::GetUpvalue(index) => {
+                    let upvalue = self.upvalue(index);
+                    let value = match &*upvalue.borrow() {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::SetUpvalue(index) => {
+                    let upvalue = self.upvalue(index);
+                    let value = self.peek(0).clone();
+                    match &mut *upvalue.borrow_mut() {
+                        Upvalue::Open(slot) => self.stack[*slot] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    };
+                }
+                OpCode::GetProperty(index) => {
+                    let name = self.name_constant(index);
+                    let Value::Instance(instance) = self.peek(0).clone() else {
+                        return Err(self.error(
+                            RuntimeErrorKind::NotAnInstance,
+                            "Only instances have properties.".to_string(),
+                        ));
+                    };
+
+                    // Fields shadow methods.
+                    let field = instance.fields.borrow().get(&name).cloned();
+                    let value = match field {
+                        Some(value) => value,
+                        None => {
+                            let receiver = Value::Instance(instance.clone());
+                            self.bind_method(&instance.class, &name, receiver)?
+                        }
+                    };
+                    self.pop();
+                    self.stack.push(value);
+                }
+                OpCode::SetProperty(index) => {
+                    let name = self.name_constant(index);
+                    let Value::Instance(instance) = self.peek(1) else {
+                        return Err(self.error(
+                            RuntimeErrorKind::NotAnInstance,
+                            "Only instances have fields.".to_string(),
+                        ));
+                    };
+
+                    let value = self.peek(0).clone();
+                    instance.fields.borrow_mut().insert(name, value.clone());
+                    self.stack.truncate(self.stack.len() - 2);
+                    self.stack.push(value);
+                }
+                OpCode::GetSuper(index) => {
+                    let name = self.name_constant(index);
+                    let Value::Class(superclass) = self.pop() else {
+                        unreachable!("'super' is always bound to a class")
+                    };
+                    let receiver = self.pop();
+                    let method = self.bind_method(&superclass, &name, receiver)?;
+                    self.stack.push(method);
+                }
+                OpCode::Equal => {
+                    let (left, right) = self.pop_operands();
+                    self.stack.push(Value::Bool(left == right));
+                }
+                OpCode::NotEqual => {
+                    let (left, right) = self.pop_operands();
+                    self.stack.push(Value::Bool(left != right));
+                }
+                OpCode::Greater => self.comparison(">", |left, right| left > right)?,
+                OpCode::GreaterEqual => self.comparison(">=", |left, right| left >= right)?,
+                OpCode::Less => self.comparison("<", |left, right| left < right)?,
+                OpCode::LessEqual => self.comparison("<=", |left, right| left <= right)?,
+                OpCode::Add => {
+                    let value = match (self.peek(1), self.peek(0)) {
+                        (Value::String(left), right) => {
+                            Value::String(format!("{left}{right}").into())
+                        }
+                        (left, Value::String(right)) => {
+                            Value::String(format!("{left}{right}").into())
+                        }
+                        (Value::Number(left), Value::Number(right)) => Value::Number(left + right),
+                        _ => return Err(self.invalid_operand("+")),
+                    };
+                    self.pop_operands();
+                    self.stack.push(value);
+                }
+                OpCode::Subtract => self.arithmetic("-", |left, right| left - right)?,
+                OpCode::Multiply => self.arithmetic("*", |left, right| left * right)?,
+                OpCode::Divide => {
+                    if let (Value::Number(_), Value::Number(right)) = (self.peek(1), self.peek(0)) {
+                        if *right == 0.0 {
+                            return Err(self.error(
+                                RuntimeErrorKind::DivisionByZero,
+                                "Division by zero.".to_string(),
+                            ));
+                        }
+                    }
+                    self.arithmetic("/", |left, right| left / right)?
+                }
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.stack.push(Value::Bool(value.is_falsey()));
+                }
+                OpCode::Negate => {
+                    let Value::Number(value) = self.peek(0) else {
+                        return Err(self.invalid_operand("-"));
+                    };
+                    let value = Value::Number(-value);
+                    self.pop();
+                    self.stack.push(value);
+                }
+                OpCode::Print => {
+                    let value = self.pop();
+                    let _ = writeln!(self.out, "{value}");
+                }
+                OpCode::Jump(offset) => self.frame_mut().ip += offset as usize,
+                OpCode::JumpIfFalse(offset) => {
+                    if self.peek(0).is_falsey() {
+                        self.frame_mut().ip += offset as usize;
+                    }
+                }
+                OpCode::Loop(offset) => self.frame_mut().ip -= offset as usize,
+                OpCode::Call(argument_count) => {
+                    let argument_count = argument_count as usize;
+                    let callee = self.peek(argument_count).clone();
+                    self.call_value(callee, argument_count)?;
+                }
+                OpCode::Closure(index) => {
+                    let Value::Function(function) = self.constant(index) else {
+                        unreachable!("closures are only made from functions")
+                    };
+                    let upvalues = function
+                        .upvalues
+                        .iter()
+                        .map(|upvalue| {
+                            if upvalue.is_local {
+                                self.capture_upvalue(self.slot(upvalue.index))
+                            } else {
+                                self.upvalue(upvalue.index)
+                            }
+                        })
+                        .collect();
+                    self.stack
+                        .push(Value::Closure(Rc::new(Closure { function, upvalues })));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().unwrap();
+                    self.close_upvalues(frame.slots);
+                    self.stack.truncate(frame.slots);
+                    if self.frames.is_empty() {
+                        return Ok(());
+                    }
+                    self.stack.push(result);
+                }
+                OpCode::Class(index) => {
+                    let name = self.name_constant(index);
+                    self.stack.push(Value::Class(Rc::new(Class::new(name))));
+                }
+                OpCode::Inherit => {
+                    let Value::Class(superclass) = self.peek(1) else {
+                        return Err(self.error(
+                            RuntimeErrorKind::SuperclassNotClass,
+                            "Superclass must be a class.".to_string(),
+                        ));
+                    };
+                    let Value::Class(subclass) = self.peek(0) else {
+                        unreachable!("only classes inherit")
+                    };
+                    let methods = superclass.methods.borrow().clone();
+                    subclass.methods.borrow_mut().extend(methods);
+                    self.pop();
+                }
+                OpCode::Method(index) => {
+                    let name = self.name_constant(index);
+                    let Value::Closure(method) = self.pop() else {
+                        unreachable!("methods are always closures")
+                    };
+                    let Value::Class(class) = self.peek(0) else {
+                        unreachable!("methods are only added to classes")
+                    };
+                    class.methods.borrow_mut().insert(name, method);
+                }
+            }
+        }
+    }
+
+    fn call_value(&mut self, callee: Value, argument_count: usize) -> Result<()> {
+        let callee_slot = self.stack.len() - argument_count - 1;
+        match callee {
+            Value::Closure(closure) => {
+                let name = closure.function.name.clone();
+                self.call(closure, argument_count, name)
+            }
+            Value::BoundMethod(bound) => {
+                self.stack[callee_slot] = bound.receiver.clone();
+                let name = bound.method.function.name.clone();
+                self.call(bound.method.clone(), argument_count, name)
+            }
+            Value::Class(class) => {
+                self.stack[callee_slot] = Value::Instance(Rc::new(Instance::new(class.clone())));
+                match class.find_method("init") {
+                    Some(initializer) => self.call(initializer, argument_count, class.name.clone()),
+                    None if argument_count != 0 => Err(self.error(
+                        RuntimeErrorKind::ArityMismatch,
+                        format!("Expected 0 arguments but got {argument_count}."),
+                    )),
+                    None => Ok(()),
+                }
+            }
+            _ => Err(self.error(
+                RuntimeErrorKind::NotCallable,
+                "Can only call functions and classes.".to_string(),
+            )),
+        }
+    }
+
+    fn call(&mut self, closure: Rc<Closure>, argument_count: usize, name: Rc<str>) -> Result<()> {
+        if argument_count != closure.function.arity {
+            return Err(self.error(
+                RuntimeErrorKind::ArityMismatch,
+                format!(
+                    "Expected {} arguments but got {}.",
+                    closure.function.arity, argument_count
+                ),
+            ));
+        }
+        if self.frames.len() == MAX_CALL_DEPTH {
+            return Err(self.error(
+                RuntimeErrorKind::StackOverflow,
+                "Stack overflow.".to_string(),
+            ));
+        }
+
+        let call_site = self.current_span();
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            slots: self.stack.len() - argument_count - 1,
+            name,
+            call_site,
+        });
+        Ok(())
+    }
+
+    fn bind_method(&self, class: &Class, name: &str, receiver: Value) -> Result<Value> {
+        match class.find_method(name) {
+            Some(method) => Ok(Value::BoundMethod(Rc::new(BoundMethod {
+                receiver,
+                method,
+            }))),
+            None => Err(self.error(
+                RuntimeErrorKind::UndefinedProperty,
+                format!("Undefined property '{name}'."),
+            )),
+        }
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(*upvalue.borrow(), Upvalue::Open(open) if open == slot));
+        if let Some(upvalue) = existing {
+            return upvalue.clone();
+        }
+
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(upvalue.clone());
+        upvalue
+    }
+
+    /// Moves the values of every upvalue pointing at or above `last` off the
+    /// stack and into the upvalue.
+    fn close_upvalues(&mut self, last: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let mut upvalue = upvalue.borrow_mut();
+            match *upvalue {
+                Upvalue::Open(slot) if slot >= last => {
+                    *upvalue = Upvalue::Closed(stack[slot].clone());
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    fn comparison(&mut self, operator: &str, compare: fn(f64, f64) -> bool) -> Result<()> {
+        let (Value::Number(left), Value::Number(right)) = (self.peek(1), self.peek(0)) else {
+            return Err(self.invalid_operand(operator));
+        };
+        let value = Value::Bool(compare(*left, *right));
+        self.pop_operands();
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn arithmetic(&mut self, operator: &str, apply: fn(f64, f64) -> f64) -> Result<()> {
+        let (Value::Number(left), Value::Number(right)) = (self.peek(1), self.peek(0)) else {
+            return Err(self.invalid_operand(operator));
+        };
+        let value = Value::Number(apply(*left, *right));
+        self.pop_operands();
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().unwrap()
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn slot(&self, slot: u32) -> usize {
+        self.frame().slots + slot as usize
+    }
+
+    fn upvalue(&self, index: u32) -> Rc<RefCell<Upvalue>> {
+        self.frame().closure.upvalues[index as usize].clone()
+    }
+
+    fn constant(&self, index: u32) -> Value {
+        self.frame().closure.function.chunk.constants[index as usize].clone()
+    }
+
+    fn name_constant(&self, index: u32) -> Rc<str> {
+        match self.constant(index) {
+            Value::String(name) => name,
+            value => unreachable!("{value} is not a name"),
+        }
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().unwrap()
+    }
+
+    fn pop_operands(&mut self) -> (Value, Value) {
+        let right = self.pop();
+        let left = self.pop();
+        (left, right)
+    }
+
+    /// The span of the instruction being executed.
+    fn current_span(&self) -> Span {
+        let frame = self.frame();
+        frame.closure.function.chunk.spans[frame.ip - 1]
+    }
+
+    fn undefined_variable(&self, name: &str) -> RuntimeError {
+        self.error(
+            RuntimeErrorKind::UndefinedVariable,
+            format!("Undefined variable '{name}'."),
+        )
+    }
+
+    fn invalid_operand(&self, operator: &str) -> RuntimeError {
+        self.error(
+            RuntimeErrorKind::InvalidOperand,
+            format!("Applying '{operator}' operator to a non number."),
+        )
+    }
+
+    /// An error at the current instruction, traced through every active
+    /// call except the top-level script.
+    fn error(&self, kind: RuntimeErrorKind, message: String) -> RuntimeError {
+        RuntimeError {
+            kind,
+            span: self.current_span(),
+            message,
+            trace: self
+                .frames
+                .iter()
+                .skip(1)
+                .rev()
+                .map(|frame| StackFrame {
+                    function: frame.name.to_string(),
+                    call_site: frame.call_site,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        interpreter::Interpreter,
+        test_utils::tests::{helper_create_stmts_from_string, helper_resolve_stmts},
+    };
+
+    /// Collects what the VM prints.
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn helper_run(source: &str) -> (String, Result<()>) {
+        let statements = helper_create_stmts_from_string(source);
+        let output = Output::default();
+        let mut vm = Vm::with_output(output.clone());
+        let result = vm.interpret(&statements, &mut ErrorReporter::quiet());
+        let printed = String::from_utf8(output.0.take()).unwrap();
+        (printed, result)
+    }
+
+    fn helper_output(source: &str) -> String {
+        let (printed, result) = helper_run(source);
+        result.unwrap();
+        printed
+    }
+
+    fn helper_error(source: &str) -> RuntimeError {
+        helper_run(source).1.unwrap_err()
+    }
+
+    /// The globals left behind by running `source`, rendered as text.
+    fn helper_globals(source: &str) -> (Vec<String>, Vec<String>) {
+        let statements = helper_create_stmts_from_string(source);
+        let interpreter = Interpreter::new();
+        helper_resolve_stmts(&statements);
+        interpreter
+            .interpret(&statements, &mut ErrorReporter::quiet())
+            .unwrap();
+        let mut tree_walker: Vec<_> = interpreter
+            .globals
+            .borrow()
+            .bindings()
+            .map(|(name, value)| format!("{name} = {value}"))
+            .collect();
+        tree_walker.sort();
+
+        let mut vm = Vm::with_output(Output::default());
+        vm.interpret(&statements, &mut ErrorReporter::quiet())
+            .unwrap();
+        let mut bytecode: Vec<_> = vm
+            .globals()
+            .map(|(name, value)| format!("{name} = {value}"))
+            .collect();
+        bytecode.sort();
+
+        (tree_walker, bytecode)
+    }
+
+    #[test]
+    fn test_arithmetic_and_strings() {
+        assert_eq!(
+            helper_output("print 1 + 2 * 3 - 4 / 8; print \"a\" + 1 + nil + true;"),
+            "6.5\na1niltrue\n"
+        );
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let source = "
+            var total = 0;
+            for (var i = 0; i < 5; i = i + 1) {
+                if (i == 2) total = total + 10; else total = total + i;
+            }
+            print total;
+            print nil or \"default\";
+            print false and 1;
+            print true ? 1 : 2;
+            print 1, 2;";
+        assert_eq!(helper_output(source), "18\ndefault\nfalse\n1\n2\n");
+    }
+
+    #[test]
+    fn test_closures_capture_variables() {
+        let source = "
+            fun counter() {
+                var count = 0;
+                fun increment() { count = count + 1; return count; }
+                return increment;
+            }
+            var a = counter();
+            var b = counter();
+            a(); a();
+            print a();
+            print b();
+            print a;";
+        assert_eq!(helper_output(source), "3\n1\n<fn increment>\n");
+    }
+
+    #[test]
+    fn test_closed_upvalues_are_shared() {
+        let source = "
+            var get; var set;
+            {
+                var value = 1;
+                fun g() { return value; }
+                fun s(v) { value = v; }
+                get = g; set = s;
+            }
+            set(2);
+            print get();";
+        assert_eq!(helper_output(source), "2\n");
+    }
+
+    #[test]
+    fn test_classes_inheritance_and_super() {
+        let source = "
+            class A {
+                init(name) { this.name = name; }
+                greet() { return \"hi \" + this.name; }
+            }
+            class B < A {
+                init(name) { super.init(name + \"!\"); }
+                greet() { return super.greet() + \"?\"; }
+            }
+            var b = B(\"bob\");
+            print b.greet();
+            print b;
+            print B;
+            print b.init(\"x\") == b;";
+        assert_eq!(helper_output(source), "hi bob!?\nB instance\nB\ntrue\n");
+    }
+
+    #[test]
+    fn test_runtime_error_kinds_and_spans() {
+        let error = helper_error("print 1;\nprint 1 / 0;");
+        assert_eq!(error.kind, RuntimeErrorKind::DivisionByZero);
+        assert_eq!((error.span.line, error.span.column), (2, 9));
+
+        assert_eq!(
+            helper_error("print -\"a\";").kind,
+            RuntimeErrorKind::InvalidOperand
+        );
+        assert_eq!(
+            helper_error("print x;").kind,
+            RuntimeErrorKind::UndefinedVariable
+        );
+        assert_eq!(helper_error("nil();").kind, RuntimeErrorKind::NotCallable);
+        assert_eq!(
+            helper_error("fun f(a) {} f();").kind,
+            RuntimeErrorKind::ArityMismatch
+        );
+        assert_eq!(
+            helper_error("class A {} A().x;").kind,
+            RuntimeErrorKind::UndefinedProperty
+        );
+        assert_eq!(
+            helper_error("var A = 1; class B < A {}").kind,
+            RuntimeErrorKind::SuperclassNotClass
+        );
+    }
+
+    #[test]
+    fn test_runtime_error_records_stack_trace() {
+        let source = "
+fun inner() { return nil + 1; }
+fun outer() { return inner(); }
+class K { init() { outer(); } }
+K();";
+        let trace: Vec<_> = helper_error(source)
+            .trace
+            .iter()
+            .map(|frame| (frame.function.clone(), frame.call_site.line))
+            .collect();
+        assert_eq!(
+            trace,
+            [
+                ("inner".to_string(), 3),
+                ("outer".to_string(), 4),
+                ("K".to_string(), 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unbounded_recursion_overflows_stack() {
+        let error = helper_error("fun f() { f(); } f();");
+        assert_eq!(error.kind, RuntimeErrorKind::StackOverflow);
+        assert_eq!(error.trace.len(), MAX_CALL_DEPTH - 1);
+    }
+
+    #[test]
+    fn test_vm_recovers_after_runtime_error() {
+        let output = Output::default();
+        let mut vm = Vm::with_output(output.clone());
+        let statements = helper_create_stmts_from_string("var a = 1; a();");
+        assert!(vm
+            .interpret(&statements, &mut ErrorReporter::quiet())
+            .is_err());
+        let statements = helper_create_stmts_from_string("print a;");
+        vm.interpret(&statements, &mut ErrorReporter::quiet())
+            .unwrap();
+        assert_eq!(String::from_utf8(output.0.take()).unwrap(), "1\n");
+    }
+
+    #[test]
+    fn test_echoing_prints_top_level_expressions() {
+        let output = Output::default();
+        let mut vm = Vm::with_output(output.clone());
+        let statements = helper_create_stmts_from_string("1 + 2; var a = 3; { a; }");
+        vm.interpret_echoing(&statements, &mut ErrorReporter::quiet())
+            .unwrap();
+        assert_eq!(String::from_utf8(output.0.take()).unwrap(), "3\n");
+    }
+
+    #[test]
+    fn test_globals_match_tree_walker() {
+        let source = "
+            fun fib(n) { return n < 2 ? n : fib(n - 1) + fib(n - 2); }
+            var f = fib(15);
+            var s = \"n\" + f + !f + !nil;
+            class P { init(x) { this.x = x; } }
+            var p = P(1);
+            var px = p.x;
+            var m = p.init;
+            var eq = 0.1 + 0.2 == 0.3;";
+        let (tree_walker, bytecode) = helper_globals(source);
+        assert_eq!(tree_walker, bytecode);
+    }
+}
@@ -0,0 +1,110 @@
+use std::fmt::Write;
+
+use super::{super::span::Span, value::Value};
+
+/// A single VM instruction. Operands are indices into the constant pool,
+/// stack slots of the current call frame, upvalue slots of the current
+/// closure, or jump offsets counted in instructions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpCode {
+    Constant(u32),
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal(u32),
+    SetLocal(u32),
+    GetGlobal(u32),
+    DefineGlobal(u32),
+    SetGlobal(u32),
+    GetUpvalue(u32),
+    SetUpvalue(u32),
+    GetProperty(u32),
+    SetProperty(u32),
+    GetSuper(u32),
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    Jump(u32),
+    JumpIfFalse(u32),
+    Loop(u32),
+    Call(u8),
+    Closure(u32),
+    CloseUpvalue,
+    Return,
+    Class(u32),
+    Inherit,
+    Method(u32),
+}
+
+/// A compiled function body: its instructions, the constants they refer to
+/// and the source span each instruction was compiled from.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<OpCode>,
+    pub constants: Vec<Value>,
+    /// The line table, with one entry per instruction in `code`. Runtime
+    /// errors are reported at the span of the instruction that raised them.
+    pub spans: Vec<Span>,
+}
+
+impl Chunk {
+    pub fn new() -> Chunk {
+        Chunk::default()
+    }
+
+    /// Appends `op` and returns its offset.
+    pub fn write(&mut self, op: OpCode, span: Span) -> usize {
+        self.code.push(op);
+        self.spans.push(span);
+        self.code.len() - 1
+    }
+
+    pub fn add_constant(&mut self, value: Value) -> u32 {
+        self.constants.push(value);
+        (self.constants.len() - 1) as u32
+    }
+
+    /// Lists the instructions one per line, with the source line they came
+    /// from and the value of any constant operand.
+    pub fn disassemble(&self, name: &str) -> String {
+        let mut result = format!("== {name} ==\n");
+        let mut previous_line = None;
+        for (offset, (op, span)) in self.code.iter().zip(&self.spans).enumerate() {
+            let line = if previous_line == Some(span.line) {
+                "   |".to_string()
+            } else {
+                format!("{:4}", span.line)
+            };
+            previous_line = Some(span.line);
+
+            let _ = write!(result, "{offset:04} {line} ");
+            let _ = match op {
+                OpCode::Constant(index)
+                | OpCode::GetGlobal(index)
+                | OpCode::DefineGlobal(index)
+                | OpCode::SetGlobal(index)
+                | OpCode::GetProperty(index)
+                | OpCode::SetProperty(index)
+                | OpCode::GetSuper(index)
+                | OpCode::Closure(index)
+                | OpCode::Class(index)
+                | OpCode::Method(index) => {
+                    writeln!(result, "{:?} '{}'", op, self.constants[*index as usize])
+                }
+                _ => writeln!(result, "{op:?}"),
+            };
+        }
+        result
+    }
+}
@@ -0,0 +1,722 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use super::{
+    super::{
+        expr::{Depth, Expr, Value as LiteralValue, Visitor as ExprVisitor},
+        span::Span,
+        stmt::{FunctionDecl, Stmt, Visitor as StmtVisitor, Void},
+        token::Token,
+        token_type::TokenType,
+    },
+    chunk::OpCode,
+    value::{Function, UpvalueRef, Value},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionType {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Local {
+    name: String,
+    depth: usize,
+    is_captured: bool,
+}
+
+/// The function currently being compiled, plus the locals in scope in it.
+struct FunctionState {
+    function: Function,
+    function_type: FunctionType,
+    locals: Vec<Local>,
+    scope_depth: usize,
+}
+
+enum Resolution {
+    Local(u32),
+    Upvalue(u32),
+    Global,
+}
+
+/// Compiles a resolved program into bytecode for the `Vm`. The resolver has
+/// already rejected invalid programs, so compilation cannot fail.
+pub struct Compiler {
+    // Innermost function last.
+    functions: RefCell<Vec<FunctionState>>,
+    // Recorded in the line table for every instruction emitted.
+    span: Cell<Span>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Compiler {
+            functions: RefCell::new(Vec::new()),
+            span: Cell::new(Span::default()),
+        }
+    }
+
+    /// Compiles a whole program into the function run as the top-level
+    /// script.
+    pub fn compile(&self, statements: &[Stmt]) -> Function {
+        self.compile_statements(statements, false)
+    }
+
+    /// Like `compile`, but the script also prints the value of every
+    /// top-level expression statement, as the REPL does.
+    pub fn compile_echoing(&self, statements: &[Stmt]) -> Function {
+        self.compile_statements(statements, true)
+    }
+
+    fn compile_statements(&self, statements: &[Stmt], echo: bool) -> Function {
+        self.begin_function("script", 0, FunctionType::Script);
+        for statement in statements {
+            match statement {
+                Stmt::Expression { expression, .. } if echo => {
+                    self.compile_expr(expression);
+                    self.emit(OpCode::Print);
+                }
+                _ => self.compile_stmt(statement),
+            }
+        }
+        self.end_function()
+    }
+
+    fn compile_stmt(&self, statement: &Stmt) {
+        statement.accept(self);
+    }
+
+    fn compile_expr(&self, expression: &Expr) {
+        expression.accept(self);
+    }
+
+    fn begin_function(&self, name: &str, arity: usize, function_type: FunctionType) {
+        // Slot zero holds the function being called, or `this` in methods.
+        let receiver = match function_type {
+            FunctionType::Method | FunctionType::Initializer => "this",
+            FunctionType::Script | FunctionType::Function => "",
+        };
+        self.functions.borrow_mut().push(FunctionState {
+            function: Function {
+                name: name.into(),
+                arity,
+                ..Function::default()
+            },
+            function_type,
+            locals: vec![Local {
+                name: receiver.to_string(),
+                depth: 0,
+                is_captured: false,
+            }],
+            scope_depth: 0,
+        });
+    }
+
+    fn end_function(&self) -> Function {
+        self.emit_return();
+        let state = self.functions.borrow_mut().pop().unwrap();
+        state.function
+    }
+
+    /// Compiles a function or method body and leaves a closure over it on
+    /// the stack.
+    fn function(&self, declaration: &FunctionDecl, function_type: FunctionType) {
+        self.begin_function(
+            &declaration.name.lexeme,
+            declaration.params.len(),
+            function_type,
+        );
+        self.begin_scope();
+        for param in &declaration.params {
+            self.add_local(&param.lexeme);
+        }
+        for statement in &declaration.body {
+            self.compile_stmt(statement);
+        }
+        let function = self.end_function();
+
+        self.span.set(declaration.name.span);
+        let constant = self.make_constant(Value::Function(Rc::new(function)));
+        self.emit(OpCode::Closure(constant));
+    }
+
+    fn emit(&self, op: OpCode) -> usize {
+        let span = self.span.get();
+        self.current(|state| state.function.chunk.write(op, span))
+    }
+
+    fn emit_return(&self) {
+        if self.current(|state| state.function_type) == FunctionType::Initializer {
+            self.emit(OpCode::GetLocal(0));
+        } else {
+            self.emit(OpCode::Nil);
+        }
+        self.emit(OpCode::Return);
+    }
+
+    /// Emits a jump with a placeholder offset, to be filled in by
+    /// `patch_jump` once the target is known.
+    fn emit_jump(&self, op: fn(u32) -> OpCode) -> usize {
+        self.emit(op(0))
+    }
+
+    /// Points the jump at `offset` to the next instruction emitted.
+    fn patch_jump(&self, offset: usize) {
+        self.current(|state| {
+            let code = &mut state.function.chunk.code;
+            let jump = (code.len() - offset - 1) as u32;
+            code[offset] = match code[offset] {
+                OpCode::Jump(_) => OpCode::Jump(jump),
+                OpCode::JumpIfFalse(_) => OpCode::JumpIfFalse(jump),
+                op => unreachable!("{op:?} is not a forward jump"),
+            };
+        });
+    }
+
+    fn emit_loop(&self, loop_start: usize) {
+        let offset = self.current(|state| state.function.chunk.code.len()) + 1 - loop_start;
+        self.emit(OpCode::Loop(offset as u32));
+    }
+
+    fn make_constant(&self, value: Value) -> u32 {
+        self.current(|state| state.function.chunk.add_constant(value))
+    }
+
+    fn identifier_constant(&self, name: &str) -> u32 {
+        self.make_constant(Value::String(name.into()))
+    }
+
+    fn current<T>(&self, f: impl FnOnce(&mut FunctionState) -> T) -> T {
+        f(self.functions.borrow_mut().last_mut().unwrap())
+    }
+
+    fn is_global_scope(&self) -> bool {
+        self.current(|state| state.scope_depth == 0)
+    }
+
+    fn begin_scope(&self) {
+        self.current(|state| state.scope_depth += 1);
+    }
+
+    fn end_scope(&self) {
+        let span = self.span.get();
+        self.current(|state| {
+            state.scope_depth -= 1;
+            while let Some(local) = state.locals.last() {
+                if local.depth <= state.scope_depth {
+                    break;
+                }
+                let op = if local.is_captured {
+                    OpCode::CloseUpvalue
+                } else {
+                    OpCode::Pop
+                };
+                state.function.chunk.write(op, span);
+                state.locals.pop();
+            }
+        });
+    }
+
+    fn add_local(&self, name: &str) {
+        self.current(|state| {
+            state.locals.push(Local {
+                name: name.to_string(),
+                depth: state.scope_depth,
+                is_captured: false,
+            })
+        });
+    }
+
+    /// Binds `name` to the value on top of the stack: as a global at the top
+    /// level, otherwise by leaving the value in place as a new local.
+    fn define_variable(&self, name: &str) {
+        if self.is_global_scope() {
+            let constant = self.identifier_constant(name);
+            self.emit(OpCode::DefineGlobal(constant));
+        } else {
+            self.add_local(name);
+        }
+    }
+
+    fn resolve(&self, name: &str) -> Resolution {
+        let mut functions = self.functions.borrow_mut();
+        let current = functions.len() - 1;
+        if let Some(slot) = resolve_local(&functions[current], name) {
+            return Resolution::Local(slot);
+        }
+        match resolve_upvalue(&mut functions, current, name) {
+            Some(index) => Resolution::Upvalue(index),
+            None => Resolution::Global,
+        }
+    }
+
+    fn get_variable(&self, name: &str) {
+        let op = match self.resolve(name) {
+            Resolution::Local(slot) => OpCode::GetLocal(slot),
+            Resolution::Upvalue(index) => OpCode::GetUpvalue(index),
+            Resolution::Global => OpCode::GetGlobal(self.identifier_constant(name)),
+        };
+        self.emit(op);
+    }
+
+    fn set_variable(&self, name: &str) {
+        let op = match self.resolve(name) {
+            Resolution::Local(slot) => OpCode::SetLocal(slot),
+            Resolution::Upvalue(index) => OpCode::SetUpvalue(index),
+            Resolution::Global => OpCode::SetGlobal(self.identifier_constant(name)),
+        };
+        self.emit(op);
+    }
+}
+
+fn resolve_local(state: &FunctionState, name: &str) -> Option<u32> {
+    state
+        .locals
+        .iter()
+        .rposition(|local| local.name == name)
+        .map(|slot| slot as u32)
+}
+
+/// Finds `name` in the functions enclosing `functions[current]`, threading
+/// an upvalue through every function in between.
+fn resolve_upvalue(functions: &mut [FunctionState], current: usize, name: &str) -> Option<u32> {
+    if current == 0 {
+        return None;
+    }
+
+    let enclosing = current - 1;
+    let upvalue = match resolve_local(&functions[enclosing], name) {
+        Some(slot) => {
+            functions[enclosing].locals[slot as usize].is_captured = true;
+            UpvalueRef {
+                is_local: true,
+                index: slot,
+            }
+        }
+        None => UpvalueRef {
+            is_local: false,
+            index: resolve_upvalue(functions, enclosing, name)?,
+        },
+    };
+
+    let upvalues = &mut functions[current].function.upvalues;
+    let index = match upvalues.iter().position(|existing| *existing == upvalue) {
+        Some(index) => index,
+        None => {
+            upvalues.push(upvalue);
+            upvalues.len() - 1
+        }
+    };
+    Some(index as u32)
+}
+
+impl ExprVisitor<Void> for Compiler {
+    fn visit_literal(&self, value: &Option<LiteralValue>) -> Void {
+        match value {
+            None | Some(LiteralValue::Nil) => self.emit(OpCode::Nil),
+            Some(LiteralValue::True) => self.emit(OpCode::True),
+            Some(LiteralValue::False) => self.emit(OpCode::False),
+            Some(LiteralValue::Number(value)) => {
+                let constant = self.make_constant(Value::Number(*value));
+                self.emit(OpCode::Constant(constant))
+            }
+            Some(LiteralValue::String(value)) => {
+                let constant = self.make_constant(Value::String(value.as_str().into()));
+                self.emit(OpCode::Constant(constant))
+            }
+            Some(value) => unreachable!("{value} is not a literal"),
+        };
+        Void
+    }
+
+    fn visit_binary(&self, left: &Expr, operation: &Token, right: &Expr) -> Void {
+        self.compile_expr(left);
+        if operation.token_type == TokenType::Comma {
+            self.emit(OpCode::Pop);
+            self.compile_expr(right);
+            return Void;
+        }
+
+        self.compile_expr(right);
+        self.span.set(operation.span);
+        let op = match operation.token_type {
+            TokenType::Minus => OpCode::Subtract,
+            TokenType::Plus => OpCode::Add,
+            TokenType::Slash => OpCode::Divide,
+            TokenType::Star => OpCode::Multiply,
+            TokenType::Greater => OpCode::Greater,
+            TokenType::GreaterEqual => OpCode::GreaterEqual,
+            TokenType::Less => OpCode::Less,
+            TokenType::LessEqual => OpCode::LessEqual,
+            TokenType::BangEqual => OpCode::NotEqual,
+            TokenType::EqualEqual => OpCode::Equal,
+            token_type => unreachable!("{token_type} is not a binary operator"),
+        };
+        self.emit(op);
+        Void
+    }
+
+    fn visit_grouping(&self, expression: &Expr) -> Void {
+        self.compile_expr(expression);
+        Void
+    }
+
+    fn visit_unary(&self, operator: &Token, right: &Expr) -> Void {
+        self.compile_expr(right);
+        self.span.set(operator.span);
+        match operator.token_type {
+            TokenType::Minus => self.emit(OpCode::Negate),
+            TokenType::Bang => self.emit(OpCode::Not),
+            token_type => unreachable!("{token_type} is not a unary operator"),
+        };
+        Void
+    }
+
+    fn visit_ternary(&self, condition: &Expr, then_branch: &Expr, else_branch: &Expr) -> Void {
+        self.compile_expr(condition);
+        let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit(OpCode::Pop);
+        self.compile_expr(then_branch);
+        let end_jump = self.emit_jump(OpCode::Jump);
+
+        self.patch_jump(else_jump);
+        self.emit(OpCode::Pop);
+        self.compile_expr(else_branch);
+        self.patch_jump(end_jump);
+        Void
+    }
+
+    fn visit_variable(&self, token: &Token, _depth: &Depth) -> Void {
+        self.span.set(token.span);
+        self.get_variable(&token.lexeme);
+        Void
+    }
+
+    fn visit_assign(&self, name: &Token, value: &Expr, _depth: &Depth) -> Void {
+        self.compile_expr(value);
+        self.span.set(name.span);
+        self.set_variable(&name.lexeme);
+        Void
+    }
+
+    fn visit_logical(&self, left: &Expr, operator: &Token, right: &Expr) -> Void {
+        self.compile_expr(left);
+        // Leaves the left operand as the result when it decides the outcome.
+        let end_jump = if operator.token_type == TokenType::Or {
+            let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+            let end_jump = self.emit_jump(OpCode::Jump);
+            self.patch_jump(else_jump);
+            end_jump
+        } else {
+            self.emit_jump(OpCode::JumpIfFalse)
+        };
+
+        self.emit(OpCode::Pop);
+        self.compile_expr(right);
+        self.patch_jump(end_jump);
+        Void
+    }
+
+    fn visit_call(&self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> Void {
+        self.compile_expr(callee);
+        for argument in arguments {
+            self.compile_expr(argument);
+        }
+        self.span.set(paren.span);
+        self.emit(OpCode::Call(arguments.len() as u8));
+        Void
+    }
+
+    fn visit_get(&self, object: &Expr, name: &Token) -> Void {
+        self.compile_expr(object);
+        self.span.set(name.span);
+        let constant = self.identifier_constant(&name.lexeme);
+        self.emit(OpCode::GetProperty(constant));
+        Void
+    }
+
+    fn visit_set(&self, object: &Expr, name: &Token, value: &Expr) -> Void {
+        self.compile_expr(object);
+        self.compile_expr(value);
+        self.span.set(name.span);
+        let constant = self.identifier_constant(&name.lexeme);
+        self.emit(OpCode::SetProperty(constant));
+        Void
+    }
+
+    fn visit_this(&self, keyword: &Token, _depth: &Depth) -> Void {
+        self.span.set(keyword.span);
+        self.get_variable("this");
+        Void
+    }
+
+    fn visit_super(&self, keyword: &Token, method: &Token, _depth: &Depth) -> Void {
+        self.span.set(keyword.span);
+        self.get_variable("this");
+        self.get_variable("super");
+        self.span.set(method.span);
+        let constant = self.identifier_constant(&method.lexeme);
+        self.emit(OpCode::GetSuper(constant));
+        Void
+    }
+}
+
+impl StmtVisitor<Void> for Compiler {
+    fn visit_expression(&self, expr: &Expr) -> Void {
+        self.compile_expr(expr);
+        self.emit(OpCode::Pop);
+        Void
+    }
+
+    fn visit_print(&self, expr: &Expr) -> Void {
+        self.compile_expr(expr);
+        self.emit(OpCode::Print);
+        Void
+    }
+
+    fn visit_var(&self, name: &Token, initializer: &Option<Expr>) -> Void {
+        match initializer {
+            Some(initializer) => self.compile_expr(initializer),
+            None => {
+                self.emit(OpCode::Nil);
+            }
+        }
+        self.span.set(name.span);
+        self.define_variable(&name.lexeme);
+        Void
+    }
+
+    fn visit_block(&self, statements: &[Stmt]) -> Void {
+        self.begin_scope();
+        for statement in statements {
+            self.compile_stmt(statement);
+        }
+        self.end_scope();
+        Void
+    }
+
+    fn visit_if(
+        &self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: &Option<Box<Stmt>>,
+    ) -> Void {
+        self.compile_expr(condition);
+        let then_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit(OpCode::Pop);
+        self.compile_stmt(then_branch);
+        let else_jump = self.emit_jump(OpCode::Jump);
+
+        self.patch_jump(then_jump);
+        self.emit(OpCode::Pop);
+        if let Some(else_branch) = else_branch {
+            self.compile_stmt(else_branch);
+        }
+        self.patch_jump(else_jump);
+        Void
+    }
+
+    fn visit_while(&self, condition: &Expr, body: &Stmt) -> Void {
+        let loop_start = self.current(|state| state.function.chunk.code.len());
+        self.compile_expr(condition);
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit(OpCode::Pop);
+        self.compile_stmt(body);
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit_jump);
+        self.emit(OpCode::Pop);
+        Void
+    }
+
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> Void {
+        let name = &declaration.name.lexeme;
+        // A local function is in scope in its own body so it can recurse.
+        if self.is_global_scope() {
+            self.function(declaration, FunctionType::Function);
+            self.define_variable(name);
+        } else {
+            self.add_local(name);
+            self.function(declaration, FunctionType::Function);
+        }
+        Void
+    }
+
+    fn visit_return(&self, keyword: &Token, value: &Option<Expr>) -> Void {
+        match value {
+            Some(value) => {
+                self.compile_expr(value);
+                self.span.set(keyword.span);
+                self.emit(OpCode::Return);
+            }
+            None => {
+                self.span.set(keyword.span);
+                self.emit_return();
+            }
+        }
+        Void
+    }
+
+    fn visit_class(
+        &self,
+        name: &Token,
+        superclass: &Option<Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) -> Void {
+        self.span.set(name.span);
+        let constant = self.identifier_constant(&name.lexeme);
+        self.emit(OpCode::Class(constant));
+        self.define_variable(&name.lexeme);
+
+        // Methods of a subclass close over an extra scope that binds `super`.
+        if let Some(superclass) = superclass {
+            self.compile_expr(superclass);
+            self.begin_scope();
+            self.add_local("super");
+
+            self.get_variable(&name.lexeme);
+            self.span.set(superclass.span());
+            self.emit(OpCode::Inherit);
+        }
+
+        self.span.set(name.span);
+        self.get_variable(&name.lexeme);
+        for method in methods {
+            let function_type = if method.name.lexeme == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.function(method, function_type);
+            let constant = self.identifier_constant(&method.name.lexeme);
+            self.emit(OpCode::Method(constant));
+        }
+        self.emit(OpCode::Pop);
+
+        if superclass.is_some() {
+            self.end_scope();
+        }
+        Void
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_utils::tests::helper_create_stmts_from_string;
+
+    fn helper_disassemble(source: &str) -> Vec<String> {
+        let statements = helper_create_stmts_from_string(source);
+        let function = Compiler::new().compile(&statements);
+        function
+            .chunk
+            .code
+            .iter()
+            .map(|op| format!("{op:?}"))
+            .collect()
+    }
+
+    #[test]
+    fn test_globals_and_locals() {
+        assert_eq!(
+            helper_disassemble("var a = 1; { var b = a; print b; }"),
+            [
+                "Constant(0)",
+                "DefineGlobal(1)",
+                "GetGlobal(2)",
+                "GetLocal(1)",
+                "Print",
+                "Pop",
+                "Nil",
+                "Return"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_jumps_are_patched_past_their_branch() {
+        assert_eq!(
+            helper_disassemble("if (true) print 1; else print 2;"),
+            [
+                "True",
+                "JumpIfFalse(4)",
+                "Pop",
+                "Constant(0)",
+                "Print",
+                "Jump(3)",
+                "Pop",
+                "Constant(1)",
+                "Print",
+                "Nil",
+                "Return"
+            ]
+        );
+        assert_eq!(
+            helper_disassemble("while (false) print 1;"),
+            [
+                "False",
+                "JumpIfFalse(4)",
+                "Pop",
+                "Constant(0)",
+                "Print",
+                "Loop(6)",
+                "Pop",
+                "Nil",
+                "Return"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_captured_locals_become_upvalues() {
+        let statements =
+            helper_create_stmts_from_string("{ var a = 1; fun f() { fun g() { return a; } } }");
+        let script = Compiler::new().compile(&statements);
+        assert!(script.chunk.code.contains(&OpCode::CloseUpvalue));
+
+        let Value::Function(f) = &script.chunk.constants[1] else {
+            panic!("Expected f");
+        };
+        let Value::Function(g) = &f.chunk.constants[0] else {
+            panic!("Expected g");
+        };
+        assert_eq!(
+            f.upvalues,
+            [UpvalueRef {
+                is_local: true,
+                index: 1
+            }]
+        );
+        assert_eq!(
+            g.upvalues,
+            [UpvalueRef {
+                is_local: false,
+                index: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn test_line_table_records_operator_spans() {
+        let statements = helper_create_stmts_from_string("print 1 +\n  2;");
+        let script = Compiler::new().compile(&statements);
+        let add = script
+            .chunk
+            .code
+            .iter()
+            .position(|op| *op == OpCode::Add)
+            .unwrap();
+        assert_eq!(script.chunk.spans[add].line, 1);
+        assert!(script.chunk.disassemble("script").contains("Add"));
+    }
+}
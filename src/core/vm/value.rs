@@ -0,0 +1,154 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{Debug, Display},
+    rc::Rc,
+};
+
+use super::chunk::Chunk;
+
+/// A value on the VM stack. Strings are shared rather than copied, and the
+/// heap objects are reference counted like the tree-walker's.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(Rc<str>),
+    /// Only found in constant pools, wrapped into a `Closure` at runtime.
+    Function(Rc<Function>),
+    Closure(Rc<Closure>),
+    BoundMethod(Rc<BoundMethod>),
+    Class(Rc<Class>),
+    Instance(Rc<Instance>),
+}
+
+impl Value {
+    pub fn is_falsey(&self) -> bool {
+        matches!(self, Value::Nil | Value::Bool(false))
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Number(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{}", value),
+            Value::Function(function) => write!(f, "{}", function),
+            Value::Closure(closure) => write!(f, "{}", closure.function),
+            Value::BoundMethod(bound) => write!(f, "{}", bound.method.function),
+            Value::Class(class) => write!(f, "{}", class.name),
+            Value::Instance(instance) => write!(f, "{} instance", instance.class.name),
+        }
+    }
+}
+
+impl PartialEq for Value {
+    /// Matches the tree-walker: numbers compare within `f64::EPSILON` and
+    /// objects compare by identity.
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(value), Value::Bool(other)) => value == other,
+            (Value::Number(value), Value::Number(other)) => (value - other).abs() < f64::EPSILON,
+            (Value::String(value), Value::String(other)) => value == other,
+            (Value::Function(value), Value::Function(other)) => Rc::ptr_eq(value, other),
+            (Value::Closure(value), Value::Closure(other)) => Rc::ptr_eq(value, other),
+            (Value::BoundMethod(value), Value::BoundMethod(other)) => Rc::ptr_eq(value, other),
+            (Value::Class(value), Value::Class(other)) => Rc::ptr_eq(value, other),
+            (Value::Instance(value), Value::Instance(other)) => Rc::ptr_eq(value, other),
+            _ => false,
+        }
+    }
+}
+
+/// Where a closure finds a variable captured from an enclosing function.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UpvalueRef {
+    /// True if the variable is a local of the immediately enclosing function,
+    /// false if it is one of that function's own upvalues.
+    pub is_local: bool,
+    pub index: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct Function {
+    pub name: Rc<str>,
+    pub arity: usize,
+    pub chunk: Chunk,
+    pub upvalues: Vec<UpvalueRef>,
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn {}>", self.name)
+    }
+}
+
+/// A captured variable. It points at a stack slot while the variable is in
+/// scope and holds the value itself once the slot has been popped.
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+#[derive(Debug)]
+pub struct Closure {
+    pub function: Rc<Function>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+#[derive(Debug)]
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: Rc<Closure>,
+}
+
+pub struct Class {
+    pub name: Rc<str>,
+    /// Includes the methods inherited from the superclass, which are copied
+    /// down when the class is created.
+    pub methods: RefCell<HashMap<Rc<str>, Rc<Closure>>>,
+}
+
+impl Class {
+    pub fn new(name: Rc<str>) -> Class {
+        Class {
+            name,
+            methods: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn find_method(&self, name: &str) -> Option<Rc<Closure>> {
+        self.methods.borrow().get(name).cloned()
+    }
+}
+
+impl Debug for Class {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+pub struct Instance {
+    pub class: Rc<Class>,
+    pub fields: RefCell<HashMap<Rc<str>, Value>>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<Class>) -> Instance {
+        Instance {
+            class,
+            fields: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+impl Debug for Instance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}